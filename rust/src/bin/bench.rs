@@ -0,0 +1,18 @@
+use haversine_generator::suite::{Suite, listings};
+
+// every listing that needs no extra arguments, sized like in the justfile
+fn main() {
+    let mut suite = Suite::new();
+
+    listings::code_alignment(&mut suite, 100_000_000);
+    listings::load_store_ports(&mut suite, 100_000_000);
+    listings::cache_size(&mut suite);
+    listings::nuke_l1(&mut suite, 1_048_576);
+    listings::simd_load_width(&mut suite, 100_000_000);
+    listings::simd_load_lines(&mut suite, 100_000_000);
+    listings::simd_write_lines(&mut suite, 100_000_000);
+    listings::non_temporal_store(&mut suite);
+    listings::prefetching(&mut suite);
+
+    suite.run_main();
+}
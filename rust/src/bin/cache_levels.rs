@@ -0,0 +1,16 @@
+use std::{env::args, fs, io::stdout};
+
+use haversine_generator::rep_tester::cache_levels::{
+    detect_cache_levels, points_from_csv, sys_caches,
+};
+
+// estimates cache sizes from a `Size,Throughput` csv, e.g. `listing_144_non_power_of_two csv`
+fn main() {
+    let path = args().nth(1).expect("usage: cache_levels <sweep.csv>");
+    let csv = fs::read_to_string(&path).expect("sweep csv must be readable");
+    let points = points_from_csv(&csv).unwrap();
+
+    detect_cache_levels(&points)
+        .print(&mut stdout(), &sys_caches(0))
+        .unwrap();
+}
@@ -1,10 +1,57 @@
 use std::time::Duration;
 
-use haversine_generator::time::TimeMeasurer;
+use haversine_generator::time::{
+    TimeMeasurer,
+    quality::{TimerQuality, tsc_info},
+    timer::TimerKind,
+};
 
 fn main() {
     let measurer = TimeMeasurer::init().unwrap();
-    let frequency = measurer.detect_clock_frequency(Duration::from_millis(100));
+    let frequency = measurer.clock_frequency(Duration::from_millis(100));
+    let calibrated = measurer.detect_clock_frequency(Duration::from_millis(100));
 
-    println!("RDTSC frequency is {}", frequency);
+    println!(
+        "RDTSC frequency is {} ({}), spin calibration says {}",
+        frequency.hz,
+        frequency.method.name(),
+        calibrated
+    );
+
+    let tsc = tsc_info();
+    let quality = measurer.quality();
+    println!("{:?}", tsc);
+    for warning in tsc.warnings() {
+        println!("note: {}", warning);
+    }
+    println!(
+        "resolution={} clocks, read overhead={:.1} clocks{}",
+        quality.resolution,
+        quality.read_overhead,
+        if quality.uses_os_clock {
+            ", using clock_gettime"
+        } else {
+            ""
+        }
+    );
+
+    println!();
+    for kind in TimerKind::ALL {
+        let Some(timer) = kind.create() else {
+            println!("{:>13}: not available", kind.name());
+            continue;
+        };
+        let frequency = timer.frequency(Duration::from_millis(100));
+        let (resolution, read_overhead) = TimerQuality::measure(|| timer.now());
+        let ns_per_tick = 1e9 / frequency.hz as f64;
+
+        println!(
+            "{:>13}: {} ticks/s ({}), resolution={:.1}ns, read overhead={:.1}ns",
+            kind.name(),
+            frequency.hz,
+            frequency.method.name(),
+            resolution as f64 * ns_per_tick,
+            read_overhead * ns_per_tick
+        );
+    }
 }
@@ -2,7 +2,7 @@ use std::env;
 
 use haversine_generator::{
     rep_run,
-    rep_tester::RepTester,
+    rep_tester::{RepTester, error::RepTestError},
     write::{RawAlloc, write_backwards, write_linear},
 };
 
@@ -15,7 +15,7 @@ fn main() {
     let bytes: usize = args.nth(1).unwrap().parse().unwrap();
     let mut rep_tester = RepTester::new().unwrap();
 
-    loop {
+    while !rep_tester.budget_exhausted() {
         for i in 0..4 {
             let with_malloc = i % 2 == 1;
             let forwards = i / 2 == 0;
@@ -45,7 +45,8 @@ fn main() {
                 },
                 check =
                     { slice.iter().fold(0 as u64, |acc, it| acc + *it as u64) > bytes as u64 - 2 }
-            );
+            )
+            .unwrap_or_else(RepTestError::exit);
         }
     }
 }
@@ -2,7 +2,7 @@ use std::env::args;
 
 use haversine_generator::{
     rep_run,
-    rep_tester::RepTester,
+    rep_tester::{RepTester, error::RepTestError},
     write::{RawAlloc, write_linear},
 };
 
@@ -26,7 +26,7 @@ fn main() {
 
     println!("Bench for {}", bytes);
 
-    loop {
+    while !rep_tester.budget_exhausted() {
         rep_run!(
             rep_tester,
             name = "fault",
@@ -39,7 +39,8 @@ fn main() {
                 write_linear(slice, 0, slice.len());
             },
             check = { slice.iter().fold(0 as u64, |acc, it| acc + *it as u64) > bytes as u64 - 2 }
-        );
+        )
+        .unwrap_or_else(RepTestError::exit);
 
         rep_run!(
             rep_tester,
@@ -54,6 +55,7 @@ fn main() {
                 write_linear(slice, 0, slice.len());
             },
             check = { slice.iter().fold(0 as u64, |acc, it| acc + *it as u64) > bytes as u64 - 2 }
-        );
+        )
+        .unwrap_or_else(RepTestError::exit);
     }
 }
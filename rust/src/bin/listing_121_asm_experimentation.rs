@@ -2,7 +2,7 @@ use std::{arch::asm, env::args};
 
 use haversine_generator::{
     core_affinity, rep_run,
-    rep_tester::{self, RepTester},
+    rep_tester::{self, RepTester, error::RepTestError},
     write::RawAlloc,
 };
 
@@ -19,7 +19,7 @@ fn main() {
 
     let mut rep_tester = RepTester::new().unwrap();
 
-    loop {
+    while !rep_tester.budget_exhausted() {
         let ptr = arr.as_mut_ptr();
         rep_run!(
             rep_tester,
@@ -41,7 +41,8 @@ fn main() {
                     );
                 }
             },
-        );
+        )
+        .unwrap_or_else(RepTestError::exit);
 
         rep_run!(
             rep_tester,
@@ -62,7 +63,8 @@ fn main() {
                     );
                 }
             },
-        );
+        )
+        .unwrap_or_else(RepTestError::exit);
         rep_run!(
             rep_tester,
             name = "nop",
@@ -82,7 +84,8 @@ fn main() {
                     );
                 }
             },
-        );
+        )
+        .unwrap_or_else(RepTestError::exit);
 
         rep_run!(
             rep_tester,
@@ -102,7 +105,8 @@ fn main() {
                     );
                 }
             },
-        );
+        )
+        .unwrap_or_else(RepTestError::exit);
 
         rep_run!(
             rep_tester,
@@ -123,5 +127,6 @@ fn main() {
                 }
             }
         )
+        .unwrap_or_else(RepTestError::exit);
     }
 }
@@ -1,30 +1,17 @@
-use std::{
-    env::args,
-    io::{Write, stdout},
-};
+use std::{env::args, io::stdout};
 
 use haversine_generator::{
     core_affinity, rep_run,
-    rep_tester::{self, RepTester},
+    rep_tester::{
+        RepTester,
+        cache_levels::sys_caches,
+        chart::Chart,
+        error::RepTestError,
+        sweep::{SweepRange, size_name},
+    },
     write::RawAlloc,
 };
 
-struct CachePerformance {
-    size: u64,
-    throughput: f64,
-}
-
-fn csv_of(items: &[CachePerformance]) -> String {
-    let mut bytes: Vec<u8> = Vec::with_capacity(32 * (1 + items.len()));
-
-    writeln!(bytes, "Size,Throughput").unwrap();
-    for item in items {
-        writeln!(bytes, "{},{}", item.size, item.throughput).unwrap();
-    }
-
-    unsafe { String::from_utf8_unchecked(bytes) }
-}
-
 fn main() {
     core_affinity::set_single_core().unwrap();
 
@@ -44,8 +31,6 @@ fn main() {
     const BUF_BITS: usize = 30;
     const BUF_SIZE: usize = 1 << BUF_BITS;
     let memory = RawAlloc::new(BUF_SIZE);
-    // let buffer = memory.as_u8_slice_mut();
-    const STEP_BITS: usize = 3;
 
     let mut i: u8 = 0;
     for item in memory.as_u8_slice_mut() {
@@ -55,31 +40,20 @@ fn main() {
 
     let mut rep_tester = RepTester::new().unwrap();
     rep_tester.print = !to_csv;
-    let mut table: Vec<CachePerformance> = Vec::with_capacity(1024);
-    for i in 14..27 {
-        for step in 0..(1 << STEP_BITS) {
-            let memory_chunk_size = (1 << i) | (step << (i - STEP_BITS));
+    let range = SweepRange::SubSteps {
+        from_bits: 14,
+        to_bits: 26,
+        step_bits: 3,
+    };
 
-            let name: String;
-            if (i - 3) >= 20 {
-                name = format!("cache_{}MB", memory_chunk_size >> 20);
-            } else if (i - 3) >= 10 {
-                name = format!("cache_{}kB", memory_chunk_size >> 10);
-            } else {
-                name = format!("cache_{}B", memory_chunk_size);
-            }
+    let table = rep_tester
+        .sweep("Size", range, |rep_tester, memory_chunk_size| {
             let iterations = (BUF_SIZE as f64 / memory_chunk_size as f64).ceil() as u64;
             let adjusted_len = iterations * memory_chunk_size;
-            // println!(
-            //     "it={}, len={}, inner_iter={}",
-            //     iterations,
-            //     adjusted_len,
-            //     memory_chunk_size / (asm::non_bin_cache::READ_SIZE as u64),
-            // );
 
             rep_run!(
                 rep_tester,
-                name = &name,
+                name = &size_name("cache", memory_chunk_size),
                 len = adjusted_len,
                 block = {
                     unsafe {
@@ -92,19 +66,18 @@ fn main() {
                         );
                     }
                 }
-            );
-            if to_csv {
-                let measurement = rep_tester.measurement(rep_tester::MeasurementKind::Best);
+            )
+        })
+        .unwrap_or_else(RepTestError::exit);
 
-                table.push(CachePerformance {
-                    size: memory_chunk_size,
-                    throughput: measurement.throughput_mb(),
-                });
-            }
-        }
-    }
     if to_csv {
-        let out = csv_of(&table);
-        write!(stdout(), "{}", out).unwrap();
+        table.write_csv(&mut stdout()).unwrap();
+    } else {
+        table.print(&mut stdout()).unwrap();
+        table.plot(&mut stdout(), &Chart::default()).unwrap();
+        table
+            .cache_levels()
+            .print(&mut stdout(), &sys_caches(0))
+            .unwrap();
     }
 }
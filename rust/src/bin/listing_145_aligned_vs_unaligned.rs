@@ -75,7 +75,8 @@ fn main() {
                     }
                 }
             },
-        );
+        )
+        .unwrap();
         let collected = rep_tester.measurement(MeasurementKind::Best);
 
         loads_measurements[idx] = collected;
@@ -29,7 +29,7 @@ fn main() {
     let size: usize = 700 * 1024 * 1024 / i64_size;
     let seed = rand::random::<i64>();
     let mut arr: Box<[MaybeUninit<i64>]> = Box::new_uninit_slice(size);
-    rep_tester.init("check", (size * i64_size) as u64, 1.0);
+    rep_tester.init("check", (size * i64_size) as u64);
     rep_tester.start_run();
     for i in 0..size / 4 {
         let i = i * 4;
@@ -151,7 +151,7 @@ fn main() {
     }
     timestamps.after_output = time_measurer.clocks_now();
 
-    let clock_frequency = time_measurer.detect_clock_frequency(Duration::from_millis(50));
+    let clock_frequency = time_measurer.clock_frequency(Duration::from_millis(50)).hz;
 
     println!("{}", format_execution_time(&timestamps, clock_frequency));
 }
@@ -39,7 +39,7 @@ fn main() {
     }
 
     with_profiling! {
-        Labels =>
+        Labels as report =>
 
         with_label! {
             Labels::Args =>
@@ -95,4 +95,11 @@ fn main() {
             }
         };
     };
+
+    if let Some(haversine) = report.label("Haversine") {
+        println!(
+            "Haversine: {:.1} clocks per pair",
+            haversine.inclusive as f64 / pairs_amount as f64
+        );
+    }
 }
@@ -0,0 +1,38 @@
+use haversine_generator::{
+    core_affinity,
+    rep_tester::{RepTester, error::RepTestError, threads::ThreadOptions},
+    write::RawAlloc,
+};
+
+// read bandwidth of N pinned threads, each over its own working set
+fn main() {
+    const READ_BITS: [usize; 5] = [14, 18, 22, 26, 30];
+    const BYTES_PER_THREAD: usize = 1 << 28;
+
+    let threads = match std::env::args().nth(1) {
+        Some(threads) => threads.parse().expect("thread count must be a number"),
+        None => core_affinity::available_cores().unwrap().len(),
+    };
+    let mut rep_tester = RepTester::new().unwrap();
+
+    for i in READ_BITS {
+        let size = 1usize << i;
+        let mask = (size - 1) as u64;
+
+        rep_tester
+            .run_threaded(
+                &format!("{} threads, {}kB each", threads, size / 1024),
+                BYTES_PER_THREAD as u64,
+                ThreadOptions::new(threads),
+                |_| {
+                    let buf = RawAlloc::new(size);
+                    buf.as_u8_slice_mut().fill(1);
+                    buf
+                },
+                |buf| unsafe {
+                    asm::cache::test_cache(BYTES_PER_THREAD as u64, mask, buf.as_u8_mut_ptr());
+                },
+            )
+            .unwrap_or_else(RepTestError::exit);
+    }
+}
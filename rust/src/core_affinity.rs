@@ -34,3 +34,48 @@ cfg_if! {
         }
     }
 }
+
+cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        // cores the process is allowed to run on, in ascending order
+        pub fn available_cores() -> Result<Vec<usize>, io::Error> {
+            unsafe {
+                let mut cpuset: libc::cpu_set_t = mem::zeroed();
+                let ret = libc::sched_getaffinity(0, size_of::<libc::cpu_set_t>(), &mut cpuset);
+                if ret != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                Ok((0..libc::CPU_SETSIZE as usize)
+                    .filter(|cpu| libc::CPU_ISSET(*cpu, &cpuset))
+                    .collect())
+            }
+        }
+
+        // pins only the calling thread
+        pub fn pin_current_thread(cpu: usize) -> Result<(), io::Error> {
+            unsafe {
+                let mut cpuset: libc::cpu_set_t = mem::zeroed();
+                libc::CPU_ZERO(&mut cpuset);
+                libc::CPU_SET(cpu, &mut cpuset);
+
+                let ret = libc::sched_setaffinity(0, size_of::<libc::cpu_set_t>(), &cpuset);
+                if ret != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                Ok(())
+            }
+        }
+    } else {
+        pub fn available_cores() -> Result<Vec<usize>, std::io::Error> {
+            let count = std::thread::available_parallelism()?.get();
+            Ok((0..count).collect())
+        }
+
+        // macOS has no hard affinity, threads stay where the scheduler puts them
+        pub fn pin_current_thread(_cpu: usize) -> Result<(), std::io::Error> {
+            Ok(())
+        }
+    }
+}
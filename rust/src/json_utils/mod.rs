@@ -12,6 +12,7 @@ pub trait AstIterTools {
     fn as_object<'a>(&'a self) -> Option<&'a Vec<KeyValuePair>>;
     fn as_array<'a>(&'a self) -> Option<&'a Vec<Ast>>;
     fn as_f64<'a>(&'a self) -> Option<&'a f64>;
+    fn as_str<'a>(&'a self) -> Option<&'a str>;
 }
 
 pub trait AstObjTools {
@@ -48,6 +49,12 @@ impl AstIterTools for Ast {
             _ => None,
         }
     }
+    fn as_str<'a>(&'a self) -> Option<&'a str> {
+        match self {
+            Ast::String(value) => Some(value),
+            _ => None,
+        }
+    }
 }
 
 pub fn prepare_data(json: String) -> JsonData {
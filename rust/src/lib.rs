@@ -11,9 +11,11 @@ pub mod core_affinity;
 pub mod json_parser;
 pub mod json_utils;
 pub mod labels;
+pub mod perf_event;
 pub mod pointer;
 pub mod rep_tester;
 pub mod simple_profiler;
+pub mod suite;
 pub mod time;
 pub mod write;
 
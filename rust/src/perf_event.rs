@@ -0,0 +1,252 @@
+// perf_event_open(2) plumbing shared by the rep tester counters and the timers
+
+#[allow(unused_imports)]
+use std::sync::atomic::{Ordering, fence};
+
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+        pub(crate) const PERF_TYPE_HARDWARE: u32 = 0;
+        pub(crate) const PERF_TYPE_SOFTWARE: u32 = 1;
+        pub(crate) const PERF_TYPE_HW_CACHE: u32 = 3;
+
+        pub(crate) const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+        pub(crate) const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+        pub(crate) const PERF_COUNT_HW_BRANCH_MISSES: u64 = 5;
+        pub(crate) const PERF_COUNT_SW_CPU_CLOCK: u64 = 0;
+        pub(crate) const PERF_COUNT_SW_CONTEXT_SWITCHES: u64 = 3;
+
+        pub(crate) const PERF_COUNT_HW_CACHE_L1D: u64 = 0;
+        pub(crate) const PERF_COUNT_HW_CACHE_LL: u64 = 2;
+        pub(crate) const PERF_COUNT_HW_CACHE_DTLB: u64 = 3;
+        pub(crate) const PERF_COUNT_HW_CACHE_OP_READ: u64 = 0;
+        pub(crate) const PERF_COUNT_HW_CACHE_RESULT_MISS: u64 = 1;
+
+        pub(crate) const PERF_FORMAT_TOTAL_TIME_ENABLED: u64 = 1 << 0;
+        pub(crate) const PERF_FORMAT_TOTAL_TIME_RUNNING: u64 = 1 << 1;
+        pub(crate) const PERF_FORMAT_GROUP: u64 = 1 << 3;
+
+        const FLAG_EXCLUDE_KERNEL: u64 = 1 << 5;
+        const FLAG_EXCLUDE_HV: u64 = 1 << 6;
+
+        // PERF_ATTR_SIZE_VER0 layout of `struct perf_event_attr`
+        #[repr(C)]
+        #[derive(Default)]
+        struct PerfEventAttr {
+            kind: u32,
+            size: u32,
+            config: u64,
+            sample_period: u64,
+            sample_type: u64,
+            read_format: u64,
+            flags: u64,
+            wakeup_events: u32,
+            bp_type: u32,
+            config1: u64,
+        }
+
+        // counts the calling thread on any cpu, joins the group of `group_fd` unless it's -1
+        pub(crate) fn open_event(
+            kind: u32,
+            config: u64,
+            group_fd: i32,
+            read_format: u64,
+        ) -> Option<OwnedFd> {
+            // hardware events count user space only, so they work with perf_event_paranoid=2,
+            // software ones like context switches only ever happen in the kernel
+            let flags = match kind {
+                PERF_TYPE_SOFTWARE => FLAG_EXCLUDE_HV,
+                _ => FLAG_EXCLUDE_KERNEL | FLAG_EXCLUDE_HV,
+            };
+            let attr = PerfEventAttr {
+                kind,
+                size: size_of::<PerfEventAttr>() as u32,
+                config,
+                read_format,
+                flags,
+                ..Default::default()
+            };
+
+            let fd = unsafe {
+                libc::syscall(
+                    libc::SYS_perf_event_open,
+                    &attr as *const PerfEventAttr,
+                    0,
+                    -1,
+                    group_fd,
+                    0,
+                )
+            };
+            if fd < 0 {
+                return None;
+            }
+
+            Some(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+        }
+
+        // `cap_user_time` of `perf_event_mmap_page.capabilities`
+        const CAP_USER_TIME: u64 = 1 << 3;
+
+        // TSC rate the kernel's `tsc` clocksource converts with, from the `time_mult`/`time_shift`
+        // of an event's mmap page, the kernel only publishes them when the TSC is stable
+        pub fn kernel_tsc_frequency() -> Option<u64> {
+            let fd = open_event(PERF_TYPE_SOFTWARE, PERF_COUNT_SW_CPU_CLOCK, -1, 0)?;
+            let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+            let page = unsafe {
+                libc::mmap(
+                    std::ptr::null_mut(),
+                    page_size,
+                    libc::PROT_READ,
+                    libc::MAP_SHARED,
+                    fd.as_raw_fd(),
+                    0,
+                )
+            };
+            if page == libc::MAP_FAILED {
+                return None;
+            }
+
+            // offsets in `struct perf_event_mmap_page`
+            let base = page as *const u8;
+            let (capabilities, time_shift, time_mult) = read_consistent(
+                || unsafe { (base.add(8) as *const u32).read_volatile() },
+                || unsafe {
+                    (
+                        (base.add(40) as *const u64).read_volatile(),
+                        (base.add(50) as *const u16).read_volatile(),
+                        (base.add(52) as *const u32).read_volatile(),
+                    )
+                },
+            );
+            unsafe { libc::munmap(page, page_size) };
+
+            if capabilities & CAP_USER_TIME == 0 {
+                return None;
+            }
+            tsc_frequency_of(time_mult, time_shift)
+        }
+
+        // user space cycles of the calling thread, a single event without a group
+        pub struct CycleCounter {
+            fd: OwnedFd,
+        }
+
+        impl CycleCounter {
+            pub fn open() -> Option<CycleCounter> {
+                open_event(PERF_TYPE_HARDWARE, PERF_COUNT_HW_CPU_CYCLES, -1, 0)
+                    .map(|fd| CycleCounter { fd })
+            }
+
+            #[inline(always)]
+            pub fn read(&self) -> Option<u64> {
+                let mut value = 0u64;
+                let bytes = size_of_val(&value);
+                let read = unsafe {
+                    libc::read(
+                        self.fd.as_raw_fd(),
+                        &mut value as *mut u64 as *mut libc::c_void,
+                        bytes,
+                    )
+                };
+
+                (read == bytes as isize).then_some(value)
+            }
+        }
+    } else {
+        pub fn kernel_tsc_frequency() -> Option<u64> {
+            None
+        }
+
+        pub struct CycleCounter;
+
+        impl CycleCounter {
+            pub fn open() -> Option<CycleCounter> {
+                None
+            }
+            #[inline(always)]
+            pub fn read(&self) -> Option<u64> {
+                None
+            }
+        }
+    }
+}
+
+// the kernel bumps `lock` before and after updating the page, a read is whole
+// only when `lock` was even and didn't change around it
+#[allow(dead_code)]
+fn read_consistent<T>(lock: impl Fn() -> u32, mut read: impl FnMut() -> T) -> T {
+    loop {
+        let before = lock();
+        fence(Ordering::Acquire);
+        let value = read();
+        fence(Ordering::Acquire);
+
+        if before & 1 == 0 && lock() == before {
+            return value;
+        }
+        std::hint::spin_loop();
+    }
+}
+
+// ns = (ticks * time_mult) >> time_shift
+#[allow(dead_code)]
+fn tsc_frequency_of(time_mult: u32, time_shift: u16) -> Option<u64> {
+    if time_mult == 0 || time_shift >= 64 {
+        return None;
+    }
+
+    Some(((1_000_000_000u128 << time_shift) / time_mult as u128) as u64)
+}
+
+#[test]
+fn converts_kernel_tsc_scale() {
+    // 2.1 GHz: 1 / 2.1 ns per tick, scaled by 2^24
+    assert_eq!(tsc_frequency_of(7_989_150, 24), Some(2_100_000_125));
+    assert_eq!(tsc_frequency_of(0, 24), None);
+}
+
+#[test]
+fn retries_reads_during_updates() {
+    use std::cell::Cell;
+
+    // odd while the kernel writes, then a finished update in between two reads
+    let locks = [3, 4, 6, 6, 6];
+    let calls = Cell::new(0);
+    let lock = || {
+        let lock = locks[calls.get()];
+        calls.set(calls.get() + 1);
+        lock
+    };
+    let mut reads = 0;
+
+    let value = read_consistent(lock, || {
+        reads += 1;
+        reads
+    });
+    assert_eq!(value, 3);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn counts_kernel_side_software_events() {
+    use std::os::fd::AsRawFd;
+
+    // refused without CAP_PERFMON under perf_event_paranoid=2
+    let Some(fd) = open_event(PERF_TYPE_SOFTWARE, PERF_COUNT_SW_CONTEXT_SWITCHES, -1, 0) else {
+        return;
+    };
+    std::thread::sleep(std::time::Duration::from_millis(1));
+    std::thread::yield_now();
+
+    let mut switches = 0u64;
+    let read = unsafe {
+        libc::read(
+            fd.as_raw_fd(),
+            &mut switches as *mut u64 as *mut libc::c_void,
+            size_of::<u64>(),
+        )
+    };
+    assert_eq!(read, size_of::<u64>() as isize);
+    assert!(switches > 0);
+}
@@ -0,0 +1,388 @@
+use std::{
+    env, fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    json_parser::parse_json,
+    json_utils::{AstIterTools, AstObjTools},
+};
+
+use super::{
+    config::{ConfigError, env_value},
+    report::{RunRecord, write_json_str},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fingerprint {
+    pub cpu_model: String,
+    // rounded to 10MHz, detection is noisy
+    pub timer_frequency_mhz: u64,
+}
+
+impl Fingerprint {
+    pub fn detect(timer_frequency: u64) -> Fingerprint {
+        Fingerprint {
+            cpu_model: cpu_model().unwrap_or_else(|| "unknown".to_string()),
+            timer_frequency_mhz: (timer_frequency + 5_000_000) / 10_000_000 * 10,
+        }
+    }
+
+    pub fn key(&self) -> String {
+        format!("{} @ {}MHz", self.cpu_model, self.timer_frequency_mhz)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn cpu_model() -> Option<String> {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+
+    cpuinfo
+        .lines()
+        .find(|line| line.starts_with("model name") || line.starts_with("Model"))
+        .and_then(|line| line.split_once(':'))
+        .map(|(_, model)| model.trim().to_string())
+}
+
+#[cfg(target_vendor = "apple")]
+fn cpu_model() -> Option<String> {
+    let mut buf = [0u8; 256];
+    let mut len = buf.len();
+    let code = unsafe {
+        libc::sysctlbyname(
+            c"machdep.cpu.brand_string".as_ptr(),
+            buf.as_mut_ptr() as *mut libc::c_void,
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if code != 0 || len == 0 {
+        return None;
+    }
+
+    // len includes the trailing nul
+    Some(String::from_utf8_lossy(&buf[..len - 1]).into_owned())
+}
+
+#[cfg(not(any(target_os = "linux", target_vendor = "apple")))]
+fn cpu_model() -> Option<String> {
+    None
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaselineEntry {
+    pub machine: String,
+    pub name: String,
+    pub bytes: u64,
+    pub best_seconds: f64,
+}
+
+impl BaselineEntry {
+    fn seconds_per_byte(&self) -> f64 {
+        self.best_seconds / self.bytes.max(1) as f64
+    }
+}
+
+#[derive(Debug)]
+pub struct Regression {
+    pub name: String,
+    pub baseline_seconds: f64,
+    pub current_seconds: f64,
+    // current / baseline time per byte
+    pub slowdown: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BaselineMode {
+    Save,
+    Check,
+}
+
+pub struct BaselineStore {
+    path: PathBuf,
+    fingerprint: Option<Fingerprint>,
+    entries: Vec<BaselineEntry>,
+    regressions: Vec<Regression>,
+    pub mode: BaselineMode,
+    // 0.05 means 5% slower than baseline is a regression
+    pub threshold: f64,
+}
+
+pub const BASELINE_ENV: &str = "REP_TEST_BASELINE";
+pub const BASELINE_MODE_ENV: &str = "REP_TEST_BASELINE_MODE";
+pub const BASELINE_THRESHOLD_ENV: &str = "REP_TEST_BASELINE_THRESHOLD";
+pub const REGRESSION_EXIT_CODE: i32 = 3;
+
+impl BaselineStore {
+    pub const DEFAULT_THRESHOLD: f64 = 0.05;
+
+    pub fn load(path: &Path, mode: BaselineMode) -> io::Result<BaselineStore> {
+        let entries = match fs::read_to_string(path) {
+            Ok(json) => parse_entries(json)
+                .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+
+        Ok(BaselineStore {
+            path: path.to_owned(),
+            fingerprint: None,
+            entries,
+            regressions: Vec::new(),
+            mode,
+            threshold: BaselineStore::DEFAULT_THRESHOLD,
+        })
+    }
+
+    // `REP_TEST_BASELINE=baseline.json REP_TEST_BASELINE_MODE=save|check REP_TEST_BASELINE_THRESHOLD=0.05`
+    pub fn from_env() -> Result<Option<BaselineStore>, ConfigError> {
+        let Some(path) = env::var_os(BASELINE_ENV) else {
+            return Ok(None);
+        };
+        let mode = match env::var(BASELINE_MODE_ENV).as_deref() {
+            Ok("save") => BaselineMode::Save,
+            Ok("check") | Err(_) => BaselineMode::Check,
+            Ok(other) => {
+                return Err(ConfigError::invalid(
+                    BASELINE_MODE_ENV,
+                    "'save' or 'check'",
+                    other,
+                ));
+            }
+        };
+
+        let mut store = BaselineStore::load(Path::new(&path), mode)
+            .map_err(|it| ConfigError::file(BASELINE_ENV, it))?;
+        if let Some(threshold) = env_value(BASELINE_THRESHOLD_ENV, "a fraction")? {
+            store.threshold = threshold;
+        }
+
+        Ok(Some(store))
+    }
+
+    pub fn entries(&self) -> &[BaselineEntry] {
+        &self.entries
+    }
+
+    pub fn regressions(&self) -> &[Regression] {
+        &self.regressions
+    }
+
+    fn machine(&mut self, timer_frequency: u64) -> String {
+        self.fingerprint
+            .get_or_insert_with(|| Fingerprint::detect(timer_frequency))
+            .key()
+    }
+
+    fn find(&self, machine: &str, name: &str) -> Option<&BaselineEntry> {
+        self.entries
+            .iter()
+            .find(|it| it.machine == machine && it.name == name)
+    }
+
+    pub fn check(&mut self, record: &RunRecord, timer_frequency: u64) -> Option<&Regression> {
+        let machine = self.machine(timer_frequency);
+        let baseline = self.find(&machine, record.name)?;
+        if record.best.time == 0.0 {
+            return None;
+        }
+
+        let current_per_byte = record.best.time / record.bytes.max(1) as f64;
+        let slowdown = current_per_byte / baseline.seconds_per_byte();
+        if slowdown <= 1.0 + self.threshold {
+            return None;
+        }
+
+        self.regressions.push(Regression {
+            name: record.name.to_string(),
+            baseline_seconds: baseline.best_seconds,
+            current_seconds: record.best.time,
+            slowdown,
+        });
+        self.regressions.last()
+    }
+
+    pub fn update(&mut self, record: &RunRecord, timer_frequency: u64) {
+        if record.best.time == 0.0 {
+            return;
+        }
+
+        let machine = self.machine(timer_frequency);
+        let entry = BaselineEntry {
+            machine,
+            name: record.name.to_string(),
+            bytes: record.bytes,
+            best_seconds: record.best.time,
+        };
+
+        match self
+            .entries
+            .iter_mut()
+            .find(|it| it.machine == entry.machine && it.name == entry.name)
+        {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let mut out: Vec<u8> = Vec::with_capacity(128 * (1 + self.entries.len()));
+        write_entries(&mut out, &self.entries)?;
+
+        fs::write(&self.path, out)
+    }
+
+    // handles a finished run according to `mode`, returns true for a regression
+    pub fn process(&mut self, record: &RunRecord, timer_frequency: u64) -> io::Result<bool> {
+        match self.mode {
+            BaselineMode::Save => {
+                self.update(record, timer_frequency);
+                self.save()?;
+                Ok(false)
+            }
+            BaselineMode::Check => Ok(self.check(record, timer_frequency).is_some()),
+        }
+    }
+}
+
+impl fmt::Display for Regression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "REGRESSION {}: {:.3} ms -> {:.3} ms ({:+.2}%)",
+            self.name,
+            self.baseline_seconds * 1000.0,
+            self.current_seconds * 1000.0,
+            (self.slowdown - 1.0) * 100.0
+        )
+    }
+}
+
+fn write_entries(out: &mut impl Write, entries: &[BaselineEntry]) -> io::Result<()> {
+    writeln!(out, "{{\"entries\": [")?;
+    for (idx, entry) in entries.iter().enumerate() {
+        write!(out, "  {{\"machine\": ")?;
+        write_json_str(out, &entry.machine)?;
+        write!(out, ", \"name\": ")?;
+        write_json_str(out, &entry.name)?;
+        write!(
+            out,
+            ", \"bytes\": {}, \"best_seconds\": {}}}",
+            entry.bytes, entry.best_seconds
+        )?;
+        if idx + 1 != entries.len() {
+            write!(out, ",")?;
+        }
+        writeln!(out)?;
+    }
+    writeln!(out, "]}}")
+}
+
+fn parse_entries(json: String) -> Result<Vec<BaselineEntry>, String> {
+    let ast = parse_json(json).map_err(|err| err.message)?;
+    let entries = ast
+        .as_object()
+        .and_then(|it| it.find_by_key("entries"))
+        .and_then(|it| it.as_array())
+        .ok_or("baseline must be {\"entries\": [...]}")?;
+
+    entries
+        .iter()
+        .map(|entry| {
+            let obj = entry
+                .as_object()
+                .ok_or("baseline entry must be an object")?;
+            let string = |key: &str| {
+                obj.find_by_key(key)
+                    .and_then(|it| it.as_str())
+                    .map(|it| it.to_string())
+                    .ok_or(format!("baseline entry must have '{}' string", key))
+            };
+            let number = |key: &str| {
+                obj.find_by_key(key)
+                    .and_then(|it| it.as_f64())
+                    .copied()
+                    .ok_or(format!("baseline entry must have '{}' number", key))
+            };
+
+            Ok(BaselineEntry {
+                machine: string("machine")?,
+                name: string("name")?,
+                bytes: number("bytes")? as u64,
+                best_seconds: number("best_seconds")?,
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn entries_roundtrip() {
+    let entries = vec![
+        BaselineEntry {
+            machine: "AMD Ryzen 5 5600X 6-Core Processor @ 3700MHz".to_string(),
+            name: "File::read \"x\"".to_string(),
+            bytes: 1 << 30,
+            best_seconds: 0.25,
+        },
+        BaselineEntry {
+            machine: "unknown @ 1000MHz".to_string(),
+            name: "cache_32kB".to_string(),
+            bytes: 4096,
+            best_seconds: 0.0015,
+        },
+    ];
+    let mut out = Vec::new();
+    write_entries(&mut out, &entries).unwrap();
+
+    let parsed = parse_entries(String::from_utf8(out).unwrap()).unwrap();
+    assert_eq!(parsed.len(), 2);
+    assert_eq!(parsed[0], entries[0]);
+    assert_eq!(parsed[1].name, entries[1].name);
+    assert!((parsed[1].best_seconds - entries[1].best_seconds).abs() < 1e-12);
+}
+
+#[test]
+fn flags_regression_beyond_threshold() {
+    use super::{PerformanceMeasurement, units::Unit};
+
+    let record = |seconds: f64| {
+        let measurement = PerformanceMeasurement {
+            bytes: 1000,
+            time: seconds,
+            clocks: seconds * 1e9,
+            ..Default::default()
+        };
+        RunRecord {
+            name: "json_parse",
+            bytes: 1000,
+            unit: Unit::Bytes,
+            runs: 10,
+            best: measurement,
+            worst: measurement,
+            avg: measurement,
+            overhead_clocks: 0,
+        }
+    };
+    let mut store = BaselineStore {
+        path: PathBuf::new(),
+        fingerprint: None,
+        entries: Vec::new(),
+        regressions: Vec::new(),
+        mode: BaselineMode::Check,
+        threshold: 0.1,
+    };
+    const FREQ: u64 = 3_000_000_000;
+
+    assert!(store.check(&record(1.0), FREQ).is_none());
+    store.update(&record(1.0), FREQ);
+    assert_eq!(store.entries().len(), 1);
+    assert_eq!(store.entries()[0].machine, Fingerprint::detect(FREQ).key());
+
+    assert!(store.check(&record(1.05), FREQ).is_none());
+    assert!(store.check(&record(0.5), FREQ).is_none());
+    let regression = store.check(&record(1.2), FREQ).unwrap();
+    assert!((regression.slowdown - 1.2).abs() < 1e-9);
+    assert_eq!(store.regressions().len(), 1);
+}
@@ -0,0 +1,257 @@
+use std::io::{self, Write};
+
+use super::sweep::{SweepTable, size_label};
+
+// a point belongs to the current plateau while it is this close to the plateau median
+const TOLERANCE: f64 = 0.15;
+// plateaus closer than this are the same level split by noise
+const MIN_DROP: f64 = 0.15;
+const MIN_PLATEAU_POINTS: usize = 2;
+// memory bandwidth keeps sinking past the last level cache (TLB reach, page walks),
+// which reads as more plateaus, those are all memory
+const MAX_LEVELS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheLevel {
+    pub level: u32,
+    // largest swept size still on the plateau
+    pub size: u64,
+    pub bandwidth_mb: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheEstimate {
+    pub levels: Vec<CacheLevel>,
+    // the last plateau, the sweep must go well past the last level cache for it to be memory
+    pub memory_mb: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SysCache {
+    pub level: u32,
+    pub size: u64,
+}
+
+struct Plateau {
+    last_size: u64,
+    values: Vec<f64>,
+}
+
+impl Plateau {
+    fn new(size: u64, throughput: f64) -> Plateau {
+        Plateau {
+            last_size: size,
+            values: vec![throughput],
+        }
+    }
+
+    fn level(&self) -> f64 {
+        let mut sorted = self.values.clone();
+        sorted.sort_unstable_by(f64::total_cmp);
+        let mid = sorted.len() / 2;
+
+        match sorted.len() % 2 {
+            0 => (sorted[mid - 1] + sorted[mid]) / 2.0,
+            _ => sorted[mid],
+        }
+    }
+
+    fn contains(&self, throughput: f64) -> bool {
+        let level = self.level();
+        (throughput - level).abs() <= TOLERANCE * level
+    }
+}
+
+// `points` are (size in bytes, throughput), transitions between plateaus are skipped
+pub fn detect_cache_levels(points: &[(u64, f64)]) -> CacheEstimate {
+    let mut points = points.to_vec();
+    points.sort_unstable_by_key(|it| it.0);
+
+    let mut plateaus: Vec<Plateau> = Vec::new();
+    let mut finish = |plateau: Plateau| {
+        if plateau.values.len() < MIN_PLATEAU_POINTS {
+            return;
+        }
+        match plateaus.last_mut() {
+            Some(prev) if plateau.level() >= prev.level() * (1.0 - MIN_DROP) => {
+                prev.last_size = plateau.last_size;
+                prev.values.extend(plateau.values);
+            }
+            _ => plateaus.push(plateau),
+        }
+    };
+
+    let mut iter = points.into_iter();
+    if let Some((size, throughput)) = iter.next() {
+        let mut current = Plateau::new(size, throughput);
+        for (size, throughput) in iter {
+            if current.contains(throughput) {
+                current.last_size = size;
+                current.values.push(throughput);
+            } else {
+                finish(std::mem::replace(
+                    &mut current,
+                    Plateau::new(size, throughput),
+                ));
+            }
+        }
+        finish(current);
+    }
+
+    // without a drop there is nothing to tell caches and memory apart
+    if plateaus.len() < 2 {
+        return CacheEstimate {
+            levels: Vec::new(),
+            memory_mb: None,
+        };
+    }
+    let memory_mb = plateaus.last().map(|it| it.level());
+    let levels = plateaus[..plateaus.len() - 1]
+        .iter()
+        .take(MAX_LEVELS)
+        .enumerate()
+        .map(|(idx, it)| CacheLevel {
+            level: idx as u32 + 1,
+            size: it.last_size,
+            bandwidth_mb: it.level(),
+        })
+        .collect();
+
+    CacheEstimate { levels, memory_mb }
+}
+
+impl CacheEstimate {
+    pub fn print(&self, out: &mut impl Write, sys: &[SysCache]) -> io::Result<()> {
+        if self.levels.is_empty() {
+            writeln!(out, "no cache levels detected")?;
+        }
+        for it in &self.levels {
+            write!(
+                out,
+                "L{}: {} @ {:.0} mb/s",
+                it.level,
+                size_label(it.size),
+                it.bandwidth_mb
+            )?;
+            match sys.iter().find(|sys| sys.level == it.level) {
+                Some(sys) => writeln!(
+                    out,
+                    "; sysfs {} ({:.2}x)",
+                    size_label(sys.size),
+                    it.size as f64 / sys.size as f64
+                )?,
+                None => writeln!(out)?,
+            }
+        }
+        if let Some(memory) = self.memory_mb {
+            writeln!(out, "memory: {:.0} mb/s", memory)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SweepTable {
+    // the parameter is expected to be the working set size in bytes
+    pub fn cache_levels(&self) -> CacheEstimate {
+        let points: Vec<(u64, f64)> = self
+            .rows
+            .iter()
+            .map(|it| (it.param, it.best.throughput_mb()))
+            .collect();
+
+        detect_cache_levels(&points)
+    }
+}
+
+// `Size,Throughput[,...]` with a header, as written by `SweepTable::write_csv`
+pub fn points_from_csv(csv: &str) -> Result<Vec<(u64, f64)>, String> {
+    csv.lines()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut columns = line.split(',');
+            let size = columns.next().and_then(|it| it.trim().parse().ok());
+            let throughput = columns.next().and_then(|it| it.trim().parse().ok());
+
+            size.zip(throughput)
+                .ok_or_else(|| format!("invalid row '{}'", line))
+        })
+        .collect()
+}
+
+// data and unified caches of `cpu`, empty when sysfs is not there
+#[cfg(target_os = "linux")]
+pub fn sys_caches(cpu: usize) -> Vec<SysCache> {
+    use std::fs;
+
+    let dir = format!("/sys/devices/system/cpu/cpu{}/cache", cpu);
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut caches: Vec<SysCache> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let read = |name: &str| fs::read_to_string(path.join(name)).ok();
+
+            if read("type")?.trim() == "Instruction" {
+                return None;
+            }
+            Some(SysCache {
+                level: read("level")?.trim().parse().ok()?,
+                size: parse_sys_size(read("size")?.trim())?,
+            })
+        })
+        .collect();
+    caches.sort_unstable_by_key(|it| it.level);
+
+    caches
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn sys_caches(_cpu: usize) -> Vec<SysCache> {
+    Vec::new()
+}
+
+// `48K`, `32M`, `1024`
+fn parse_sys_size(size: &str) -> Option<u64> {
+    let (digits, multiplier) = match size.as_bytes().last()? {
+        b'K' => (&size[..size.len() - 1], 1 << 10),
+        b'M' => (&size[..size.len() - 1], 1 << 20),
+        b'G' => (&size[..size.len() - 1], 1 << 30),
+        _ => (size, 1),
+    };
+
+    digits.parse::<u64>().ok().map(|it| it * multiplier)
+}
+
+#[test]
+fn parses_sys_sizes() {
+    assert_eq!(parse_sys_size("48K"), Some(48 << 10));
+    assert_eq!(parse_sys_size("32M"), Some(32 << 20));
+    assert_eq!(parse_sys_size("512"), Some(512));
+    assert_eq!(parse_sys_size("K"), None);
+    assert_eq!(parse_sys_size(""), None);
+}
+
+#[test]
+fn detects_zen3_levels() {
+    let points = points_from_csv(include_str!("../../../zen3_cache.csv")).unwrap();
+    let estimate = detect_cache_levels(&points);
+
+    let sizes: Vec<u64> = estimate.levels.iter().map(|it| it.size).collect();
+    assert_eq!(sizes, [32 << 10, 512 << 10, 15 << 20]);
+    assert!((estimate.levels[0].bandwidth_mb - 243_000.0).abs() < 5_000.0);
+    assert!((estimate.levels[1].bandwidth_mb - 122_000.0).abs() < 5_000.0);
+    assert!(estimate.memory_mb.unwrap() < 50_000.0);
+}
+
+#[test]
+fn single_plateau_has_no_levels() {
+    let points: Vec<(u64, f64)> = (10..20).map(|bits| (1 << bits, 100.0)).collect();
+    let estimate = detect_cache_levels(&points);
+
+    assert!(estimate.levels.is_empty());
+    assert_eq!(estimate.memory_mb, None);
+}
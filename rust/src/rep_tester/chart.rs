@@ -0,0 +1,260 @@
+use std::io::{self, Write};
+
+use super::sweep::{SweepTable, size_label};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chart {
+    // plot area, without axes and labels
+    pub width: usize,
+    pub height: usize,
+    // eighth blocks instead of `#`, 8x the vertical resolution
+    pub unicode: bool,
+    pub log_x: bool,
+}
+
+impl Default for Chart {
+    fn default() -> Self {
+        Chart {
+            width: 72,
+            height: 16,
+            unicode: true,
+            log_x: true,
+        }
+    }
+}
+
+const Y_LABEL_WIDTH: usize = 10;
+const EIGHTHS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+impl Chart {
+    pub fn ascii(mut self) -> Self {
+        self.unicode = false;
+        self
+    }
+
+    pub fn linear(mut self) -> Self {
+        self.log_x = false;
+        self
+    }
+
+    // step chart of `(x, y)` over a log2 (or linear) x axis, each point holds its value until the next one,
+    // y starts at zero so drops are not exaggerated
+    pub fn render(&self, out: &mut impl Write, points: &[(u64, f64)]) -> io::Result<()> {
+        let mut points: Vec<(u64, f64)> = points
+            .iter()
+            .copied()
+            .filter(|(x, y)| (*x > 0 || !self.log_x) && y.is_finite())
+            .collect();
+        points.sort_unstable_by_key(|it| it.0);
+        if points.is_empty() || self.width == 0 || self.height == 0 {
+            return writeln!(out, "(no data)");
+        }
+
+        let columns = self.columns(&points);
+        let max_y = points.iter().map(|it| it.1).fold(0.0, f64::max);
+        let per_row = if self.unicode { 8 } else { 1 };
+        let cells = self.height * per_row;
+        let filled: Vec<Option<usize>> = columns
+            .iter()
+            .map(|it| {
+                it.map(|y| {
+                    if max_y == 0.0 {
+                        0
+                    } else {
+                        ((y / max_y) * cells as f64).round() as usize
+                    }
+                })
+            })
+            .collect();
+
+        // on the top row of a bar of half the max, rounded like the bars are
+        let mid_row = ((cells as f64 / 2.0).round() as usize)
+            .div_ceil(per_row)
+            .saturating_sub(1);
+        for row in (0..self.height).rev() {
+            let label = match row {
+                _ if row + 1 == self.height => format!("{:.0}", max_y),
+                _ if row == mid_row && self.height > 2 => format!("{:.0}", max_y / 2.0),
+                _ => String::new(),
+            };
+            write!(out, "{:>w$} {}", label, self.vertical(), w = Y_LABEL_WIDTH)?;
+
+            let line: String = filled
+                .iter()
+                .map(|it| self.cell(it.unwrap_or(0), row))
+                .collect();
+            writeln!(out, "{}", line.trim_end())?;
+        }
+
+        let (corner, horizontal) = match self.unicode {
+            true => ('└', "─"),
+            false => ('+', "-"),
+        };
+        writeln!(
+            out,
+            "{:>w$} {}{}",
+            0,
+            corner,
+            horizontal.repeat(self.width),
+            w = Y_LABEL_WIDTH
+        )?;
+        writeln!(
+            out,
+            "{:>w$}  {}",
+            "",
+            self.x_labels(&points).trim_end(),
+            w = Y_LABEL_WIDTH
+        )
+    }
+
+    fn vertical(&self) -> char {
+        if self.unicode { '│' } else { '|' }
+    }
+
+    fn cell(&self, filled: usize, row: usize) -> char {
+        match self.unicode {
+            true => match filled.saturating_sub(row * 8) {
+                0 => ' ',
+                it => EIGHTHS[it.min(8) - 1],
+            },
+            false if filled > row => '#',
+            false => ' ',
+        }
+    }
+
+    fn scale(&self, x: u64) -> f64 {
+        if self.log_x {
+            (x as f64).log2()
+        } else {
+            x as f64
+        }
+    }
+
+    fn range(&self, points: &[(u64, f64)]) -> (f64, f64) {
+        let min = self.scale(points[0].0);
+        let max = self.scale(points[points.len() - 1].0);
+        (min, (max - min).max(f64::EPSILON))
+    }
+
+    fn column_of(&self, x: u64, (min, span): (f64, f64)) -> usize {
+        let position = (self.scale(x) - min) / span;
+        ((position * (self.width - 1) as f64).round() as usize).min(self.width - 1)
+    }
+
+    fn columns(&self, points: &[(u64, f64)]) -> Vec<Option<f64>> {
+        let range = self.range(points);
+        let mut columns = vec![None; self.width];
+
+        for (idx, (x, y)) in points.iter().enumerate() {
+            let from = self.column_of(*x, range);
+            let to = match points.get(idx + 1) {
+                Some(next) => self.column_of(next.0, range).max(from + 1),
+                None => from + 1,
+            };
+            for column in &mut columns[from..to.min(self.width)] {
+                *column = Some(*y);
+            }
+        }
+
+        columns
+    }
+
+    // sizes at powers of two for log2 axis, 5 evenly spaced numbers otherwise,
+    // skipping the ones which would overlap
+    fn x_labels(&self, points: &[(u64, f64)]) -> String {
+        let range = self.range(points);
+        let (first, last) = (points[0].0, points[points.len() - 1].0);
+        let mut line = vec![' '; self.width + Y_LABEL_WIDTH];
+        let mut free_from = 0;
+
+        let ticks: Vec<(u64, String)> = match self.log_x {
+            true => (first.next_power_of_two().ilog2()..=last.ilog2())
+                .map(|bits| (1 << bits, size_label(1 << bits)))
+                .collect(),
+            false => (0..=4)
+                .map(|tick| first + (last - first) * tick / 4)
+                .map(|x| (x, x.to_string()))
+                .collect(),
+        };
+        for (x, label) in ticks {
+            let label: Vec<char> = label.chars().collect();
+            let column = self.column_of(x, range);
+            if column < free_from || column + label.len() > line.len() {
+                continue;
+            }
+
+            line[column..column + label.len()].copy_from_slice(&label);
+            free_from = column + label.len() + 1;
+        }
+
+        line.into_iter().collect()
+    }
+}
+
+impl SweepTable {
+    // best throughput per parameter, the parameter is expected to be a size in bytes
+    pub fn plot(&self, out: &mut impl Write, chart: &Chart) -> io::Result<()> {
+        let points: Vec<(u64, f64)> = self
+            .rows
+            .iter()
+            .map(|it| (it.param, it.best.throughput_mb()))
+            .collect();
+
+        writeln!(out, "best mb/s by {}", self.param_name)?;
+        chart.render(out, &points)
+    }
+}
+
+#[cfg(test)]
+fn render(chart: Chart, points: &[(u64, f64)]) -> String {
+    let mut out = Vec::new();
+    chart.render(&mut out, points).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn renders_cliffs() {
+    let points = [
+        (16 << 10, 200.0),
+        (32 << 10, 200.0),
+        (64 << 10, 100.0),
+        (128 << 10, 100.0),
+        (256 << 10, 50.0),
+    ];
+    let chart = Chart {
+        width: 17,
+        height: 4,
+        unicode: true,
+        log_x: true,
+    };
+
+    insta::assert_snapshot!(render(chart, &points));
+    insta::assert_snapshot!(render(chart.ascii(), &points));
+}
+
+#[test]
+fn renders_linear_axis() {
+    let points: Vec<(u64, f64)> = (0..=8).map(|x| (x * 100, (8 - x) as f64)).collect();
+    let chart = Chart {
+        width: 9,
+        height: 2,
+        unicode: false,
+        log_x: false,
+    };
+
+    assert_eq!(
+        render(chart, &points),
+        concat!(
+            "         8 |###\n",
+            "           |#######\n",
+            "         0 +---------\n",
+            "            0 200 600\n",
+        )
+    );
+}
+
+#[test]
+fn renders_without_data() {
+    assert_eq!(render(Chart::default(), &[]), "(no data)\n");
+    assert_eq!(render(Chart::default(), &[(0, 1.0)]), "(no data)\n");
+}
@@ -0,0 +1,252 @@
+use std::io::{self, Write};
+
+use super::PerformanceMeasurement;
+
+// a finished run detached from the tester, so the next `rep_run!` can reuse it
+pub struct RunSnapshot {
+    pub name: String,
+    pub bytes: u64,
+    pub best: PerformanceMeasurement,
+    // clocks of every run, empty unless `retain_samples` was on
+    pub clocks: Vec<u64>,
+}
+
+impl RunSnapshot {
+    fn clocks_per_byte(&self) -> Vec<f64> {
+        let bytes = self.bytes.max(1) as f64;
+        self.clocks.iter().map(|it| *it as f64 / bytes).collect()
+    }
+}
+
+pub struct Comparison {
+    pub baseline: String,
+    pub variant: String,
+    // baseline time / variant time for the same amount of bytes, > 1.0 means the variant is faster
+    pub best_speedup: f64,
+    // geometric mean speedup over all retained runs
+    pub speedup: Option<f64>,
+    pub confidence_interval: Option<(f64, f64)>,
+    // two-sided Mann-Whitney U
+    pub p_value: Option<f64>,
+}
+
+impl Comparison {
+    pub const ALPHA: f64 = 0.05;
+    // 95% two sided
+    const Z: f64 = 1.959_963_984_540_054;
+
+    pub fn of(baseline: &RunSnapshot, variant: &RunSnapshot) -> Comparison {
+        let best_speedup = if baseline.best.time == 0.0 || variant.best.time == 0.0 {
+            0.0
+        } else {
+            (baseline.best.time / baseline.bytes as f64)
+                / (variant.best.time / variant.bytes as f64)
+        };
+
+        let mut comparison = Comparison {
+            baseline: baseline.name.clone(),
+            variant: variant.name.clone(),
+            best_speedup,
+            speedup: None,
+            confidence_interval: None,
+            p_value: None,
+        };
+        if baseline.clocks.len() < 2 || variant.clocks.len() < 2 {
+            return comparison;
+        }
+
+        let a = baseline.clocks_per_byte();
+        let b = variant.clocks_per_byte();
+
+        let (a_mean, a_var) = log_mean_var(&a);
+        let (b_mean, b_var) = log_mean_var(&b);
+        let log_ratio = a_mean - b_mean;
+        let std_err = (a_var / a.len() as f64 + b_var / b.len() as f64).sqrt();
+
+        comparison.speedup = Some(log_ratio.exp());
+        comparison.confidence_interval = Some((
+            (log_ratio - Comparison::Z * std_err).exp(),
+            (log_ratio + Comparison::Z * std_err).exp(),
+        ));
+        comparison.p_value = Some(mann_whitney_p(&a, &b));
+
+        comparison
+    }
+
+    pub fn is_significant(&self) -> bool {
+        self.p_value.is_some_and(|p| p < Comparison::ALPHA)
+    }
+}
+
+pub fn print_comparisons(out: &mut impl Write, comparisons: &[Comparison]) -> io::Result<()> {
+    let name_width = comparisons
+        .iter()
+        .map(|it| it.baseline.len().max(it.variant.len()))
+        .max()
+        .unwrap_or(0)
+        .max("baseline".len());
+
+    writeln!(
+        out,
+        "{:<w$} | {:<w$} | {:>8} | {:>8} | {:>17} | {:>8}",
+        "baseline",
+        "variant",
+        "best",
+        "speedup",
+        "95% ci",
+        "p",
+        w = name_width
+    )?;
+    for it in comparisons {
+        let speedup = it
+            .speedup
+            .map_or("-".to_string(), |it| format!("{:.3}x", it));
+        let ci = it
+            .confidence_interval
+            .map_or("-".to_string(), |(lo, hi)| format!("{:.3}..{:.3}", lo, hi));
+        let p = match it.p_value {
+            Some(p) if it.is_significant() => format!("{:.4}*", p),
+            Some(p) => format!("{:.4}", p),
+            None => "-".to_string(),
+        };
+
+        writeln!(
+            out,
+            "{:<w$} | {:<w$} | {:>8} | {:>8} | {:>17} | {:>8}",
+            it.baseline,
+            it.variant,
+            format!("{:.3}x", it.best_speedup),
+            speedup,
+            ci,
+            p,
+            w = name_width
+        )?;
+    }
+
+    Ok(())
+}
+
+fn log_mean_var(samples: &[f64]) -> (f64, f64) {
+    let n = samples.len() as f64;
+    let mean = samples.iter().map(|it| it.ln()).sum::<f64>() / n;
+    let var = samples
+        .iter()
+        .map(|it| (it.ln() - mean) * (it.ln() - mean))
+        .sum::<f64>()
+        / (n - 1.0);
+
+    (mean, var)
+}
+
+// normal approximation with tie correction, fine for the hundreds of runs rep tests produce
+fn mann_whitney_p(a: &[f64], b: &[f64]) -> f64 {
+    let mut all: Vec<(f64, bool)> = a
+        .iter()
+        .map(|it| (*it, true))
+        .chain(b.iter().map(|it| (*it, false)))
+        .collect();
+    all.sort_unstable_by(|x, y| x.0.total_cmp(&y.0));
+
+    let n = all.len();
+    let mut rank_sum_a = 0.0;
+    let mut tie_term = 0.0;
+    let mut i = 0;
+    while i < n {
+        let mut j = i;
+        while j + 1 < n && all[j + 1].0 == all[i].0 {
+            j += 1;
+        }
+        let ties = (j - i + 1) as f64;
+        let avg_rank = (i + j) as f64 / 2.0 + 1.0;
+        for item in &all[i..=j] {
+            if item.1 {
+                rank_sum_a += avg_rank;
+            }
+        }
+        tie_term += ties * ties * ties - ties;
+        i = j + 1;
+    }
+
+    let n1 = a.len() as f64;
+    let n2 = b.len() as f64;
+    let u = rank_sum_a - n1 * (n1 + 1.0) / 2.0;
+    let mean_u = n1 * n2 / 2.0;
+    let total = n1 + n2;
+    let var_u = n1 * n2 / 12.0 * ((total + 1.0) - tie_term / (total * (total - 1.0)));
+    if var_u <= 0.0 {
+        return 1.0;
+    }
+
+    let z = (u - mean_u).abs() / var_u.sqrt();
+    erfc(z / std::f64::consts::SQRT_2).min(1.0)
+}
+
+// Abramowitz & Stegun 7.1.26, |error| < 1.5e-7
+fn erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x);
+    let poly = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+
+    poly * (-x * x).exp()
+}
+
+#[cfg(test)]
+fn snapshot(name: &str, clocks: Vec<u64>) -> RunSnapshot {
+    let best = *clocks.iter().min().unwrap();
+    RunSnapshot {
+        name: name.to_string(),
+        bytes: 1000,
+        best: PerformanceMeasurement {
+            bytes: 1000,
+            time: best as f64 / 1e9,
+            clocks: best as f64,
+            ..Default::default()
+        },
+        clocks,
+    }
+}
+
+#[test]
+fn erfc_approximation() {
+    assert!((erfc(0.0) - 1.0).abs() < 1e-6);
+    assert!((erfc(1.0) - 0.157_299_207).abs() < 1e-6);
+    assert!((erfc(2.0) - 0.004_677_735).abs() < 1e-6);
+}
+
+#[test]
+fn detects_speedup() {
+    let baseline = snapshot("baseline", (0..100).map(|it| 2000 + it * 3).collect());
+    let variant = snapshot("variant", (0..100).map(|it| 1000 + it * 2).collect());
+    let comparison = Comparison::of(&baseline, &variant);
+
+    assert_eq!(comparison.best_speedup, 2.0);
+    let speedup = comparison.speedup.unwrap();
+    let (lo, hi) = comparison.confidence_interval.unwrap();
+    assert!(lo < speedup && speedup < hi);
+    assert!(speedup > 1.9 && speedup < 2.1);
+    assert!(comparison.is_significant());
+}
+
+#[test]
+fn same_distribution_is_not_significant() {
+    let baseline = snapshot("a", (0..100).map(|it| 1000 + (it * 37) % 100).collect());
+    let variant = snapshot("b", (0..100).map(|it| 1000 + (it * 53) % 100).collect());
+    let comparison = Comparison::of(&baseline, &variant);
+
+    assert!(!comparison.is_significant());
+    assert!((comparison.speedup.unwrap() - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn without_samples_only_best_is_compared() {
+    let mut baseline = snapshot("a", vec![2000]);
+    let variant = snapshot("b", vec![1000]);
+    baseline.clocks.clear();
+    let comparison = Comparison::of(&baseline, &variant);
+
+    assert_eq!(comparison.best_speedup, 2.0);
+    assert!(comparison.speedup.is_none());
+    assert!(comparison.p_value.is_none());
+}
@@ -0,0 +1,281 @@
+use std::{
+    env, fmt,
+    io::{IsTerminal, stdout},
+    str::FromStr,
+    time::Duration,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepConfig {
+    // stop once there was no new minimum for this long
+    pub timeout: Duration,
+    pub min_runs: u64,
+    pub max_runs: Option<u64>,
+    // hard wall time cap for all tests of a tester together, counted from its first init,
+    // wins over `min_runs`
+    pub budget: Option<Duration>,
+    // stop once stddev / mean of clocks drops below it
+    pub converge_below: Option<f64>,
+    // report clocks minus the calibrated cost of an empty start_run/end_run pair
+    pub subtract_overhead: bool,
+    // serialized timer reads right around the block, for tiny loops where plain reads
+    // get reordered into or out of the measured window
+    pub precise_reads: bool,
+}
+
+pub const TIMEOUT_ENV: &str = "REP_TEST_TIMEOUT";
+pub const MIN_RUNS_ENV: &str = "REP_TEST_MIN_RUNS";
+pub const MAX_RUNS_ENV: &str = "REP_TEST_MAX_RUNS";
+pub const BUDGET_ENV: &str = "REP_TEST_BUDGET";
+pub const CONVERGE_ENV: &str = "REP_TEST_CONVERGE";
+pub const SUBTRACT_OVERHEAD_ENV: &str = "REP_TEST_SUBTRACT_OVERHEAD";
+pub const PRECISE_ENV: &str = "REP_TEST_PRECISE";
+pub const OUTPUT_ENV: &str = "REP_TEST_OUTPUT";
+pub const PROGRESS_ENV: &str = "REP_TEST_PROGRESS";
+
+impl Default for RepConfig {
+    fn default() -> Self {
+        RepConfig {
+            timeout: RepConfig::DEFAULT_TIMEOUT,
+            min_runs: 0,
+            max_runs: None,
+            budget: None,
+            converge_below: None,
+            subtract_overhead: false,
+            precise_reads: cfg!(feature = "precise_rep_test"),
+        }
+    }
+}
+
+impl RepConfig {
+    #[cfg(feature = "precise_rep_test")]
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+    #[cfg(not(feature = "precise_rep_test"))]
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+
+    // relative stddev of a handful of runs is meaningless
+    pub const CONVERGE_MIN_RUNS: u64 = 10;
+
+    // REP_TEST_TIMEOUT/REP_TEST_BUDGET in seconds, REP_TEST_CONVERGE as a fraction (0.01 = 1%)
+    pub fn from_env() -> Result<RepConfig, ConfigError> {
+        let mut config = RepConfig::default();
+
+        if let Some(secs) = env_value::<f64>(TIMEOUT_ENV, "seconds")? {
+            config.timeout = Duration::from_secs_f64(secs);
+        }
+        config.min_runs = env_value(MIN_RUNS_ENV, "an integer")?.unwrap_or(config.min_runs);
+        config.max_runs = env_value(MAX_RUNS_ENV, "an integer")?.or(config.max_runs);
+        config.budget = env_value::<f64>(BUDGET_ENV, "seconds")?
+            .map(Duration::from_secs_f64)
+            .or(config.budget);
+        config.converge_below = env_value(CONVERGE_ENV, "a fraction")?.or(config.converge_below);
+        config.subtract_overhead |= env::var_os(SUBTRACT_OVERHEAD_ENV).is_some();
+        config.precise_reads |= env::var_os(PRECISE_ENV).is_some();
+
+        Ok(config)
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_min_runs(mut self, runs: u64) -> Self {
+        self.min_runs = runs;
+        self
+    }
+
+    pub fn with_max_runs(mut self, runs: u64) -> Self {
+        self.max_runs = Some(runs);
+        self
+    }
+
+    pub fn with_budget(mut self, budget: Duration) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    pub fn with_convergence(mut self, relative_std_dev: f64) -> Self {
+        self.converge_below = Some(relative_std_dev);
+        self
+    }
+
+    pub fn with_overhead_subtraction(mut self) -> Self {
+        self.subtract_overhead = true;
+        self
+    }
+
+    pub fn with_precise_reads(mut self) -> Self {
+        self.precise_reads = true;
+        self
+    }
+}
+
+// an env variable which is set, but can't be used
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    pub name: &'static str,
+    pub message: String,
+}
+
+impl ConfigError {
+    pub fn invalid(name: &'static str, expected: &str, value: &str) -> ConfigError {
+        ConfigError {
+            name,
+            message: format!("must be {}, got '{}'", expected, value),
+        }
+    }
+
+    pub fn file(name: &'static str, error: std::io::Error) -> ConfigError {
+        ConfigError {
+            name,
+            message: format!("file can't be opened: {}", error),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+pub(crate) fn env_value<T: FromStr>(
+    name: &'static str,
+    expected: &str,
+) -> Result<Option<T>, ConfigError> {
+    let Ok(value) = env::var(name) else {
+        return Ok(None);
+    };
+
+    match value.replace('_', "").parse() {
+        Ok(parsed) => Ok(Some(parsed)),
+        Err(_) => Err(ConfigError::invalid(name, expected, &value)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputMode {
+    // redraws the current best run in place with cursor escapes
+    Interactive,
+    // only final results per test, one line each, for logs and CI
+    Plain,
+}
+
+impl OutputMode {
+    // `REP_TEST_OUTPUT=plain|interactive`, otherwise plain unless stdout is a terminal
+    pub fn detect() -> Result<OutputMode, ConfigError> {
+        match env::var(OUTPUT_ENV).as_deref() {
+            Ok("plain") => Ok(OutputMode::Plain),
+            Ok("interactive") => Ok(OutputMode::Interactive),
+            Ok(other) => Err(ConfigError::invalid(
+                OUTPUT_ENV,
+                "'plain' or 'interactive'",
+                other,
+            )),
+            Err(_) if stdout().is_terminal() => Ok(OutputMode::Interactive),
+            Err(_) => Ok(OutputMode::Plain),
+        }
+    }
+
+    pub fn progress_from_env() -> bool {
+        env::var_os(PROGRESS_ENV).is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    Timeout,
+    MaxRuns,
+    Budget,
+    Converged,
+}
+
+pub(super) struct StopState {
+    pub runs: u64,
+    pub now: u64,
+    pub try_before: u64,
+    pub budget_until: Option<u64>,
+    pub relative_std_dev: f64,
+}
+
+impl RepConfig {
+    pub(super) fn stop_reason(&self, state: &StopState) -> Option<StopReason> {
+        if self.max_runs.is_some_and(|max| state.runs >= max) {
+            return Some(StopReason::MaxRuns);
+        }
+        if state.runs > 0 && state.budget_until.is_some_and(|until| state.now >= until) {
+            return Some(StopReason::Budget);
+        }
+        if state.runs < self.min_runs {
+            return None;
+        }
+        if let Some(threshold) = self.converge_below
+            && state.runs >= RepConfig::CONVERGE_MIN_RUNS
+            && state.relative_std_dev < threshold
+        {
+            return Some(StopReason::Converged);
+        }
+        if state.now >= state.try_before {
+            return Some(StopReason::Timeout);
+        }
+
+        None
+    }
+}
+
+#[test]
+fn stop_criteria() {
+    let state = |runs: u64, now: u64, relative_std_dev: f64| StopState {
+        runs,
+        now,
+        try_before: 100,
+        budget_until: Some(1000),
+        relative_std_dev,
+    };
+    let config = RepConfig::default()
+        .with_min_runs(5)
+        .with_max_runs(50)
+        .with_convergence(0.01);
+
+    assert_eq!(config.stop_reason(&state(0, 10, 1.0)), None);
+    // min runs hold off the timeout, but not the budget
+    assert_eq!(config.stop_reason(&state(3, 200, 1.0)), None);
+    assert_eq!(
+        config.stop_reason(&state(3, 1000, 1.0)),
+        Some(StopReason::Budget)
+    );
+    assert_eq!(
+        config.stop_reason(&state(6, 200, 1.0)),
+        Some(StopReason::Timeout)
+    );
+    assert_eq!(config.stop_reason(&state(6, 10, 0.001)), None);
+    assert_eq!(
+        config.stop_reason(&state(10, 10, 0.001)),
+        Some(StopReason::Converged)
+    );
+    assert_eq!(
+        config.stop_reason(&state(50, 10, 1.0)),
+        Some(StopReason::MaxRuns)
+    );
+    assert_eq!(RepConfig::default().stop_reason(&state(1, 99, 0.0)), None);
+}
+
+#[test]
+fn rejects_malformed_env_values() {
+    // not read by anything else, tests run in parallel
+    const NAME: &str = "REP_TEST_MALFORMED_FOR_TEST";
+    assert_eq!(env_value::<u64>(NAME, "an integer"), Ok(None));
+
+    unsafe { env::set_var(NAME, "1_000") };
+    assert_eq!(env_value::<u64>(NAME, "an integer"), Ok(Some(1000)));
+
+    unsafe { env::set_var(NAME, "fast") };
+    let error = env_value::<u64>(NAME, "an integer").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "REP_TEST_MALFORMED_FOR_TEST must be an integer, got 'fast'"
+    );
+}
@@ -0,0 +1,141 @@
+use std::{fmt, io};
+
+use super::baseline::REGRESSION_EXIT_CODE;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RepTestError {
+    DoubleStart,
+    EndWithoutStart,
+    TimeTravel,
+    // 1-based index of the run which didn't pass
+    CheckFailed { run: u64 },
+    ReInit,
+    // start_run/end_run outside of init..finish
+    NotTesting,
+    // threaded runs: the affinity mask can't be read
+    NoAffinity,
+    PinFailed { thread: usize },
+    // the tester's timer kind can't be created on a worker
+    NoTimer,
+    // `setup` or `block` of the worker panicked
+    WorkerPanicked { thread: usize },
+    // the test itself ran, writing its results didn't
+    ReportFailed { kind: io::ErrorKind },
+    SeriesFailed { kind: io::ErrorKind },
+    BaselineFailed { kind: io::ErrorKind },
+    // slower than the stored baseline, see `BaselineStore::threshold`
+    Regressed,
+}
+
+impl fmt::Display for RepTestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepTestError::DoubleStart => write!(f, "Double start have occured"),
+            RepTestError::EndWithoutStart => write!(f, "end_run without start_run"),
+            RepTestError::TimeTravel => write!(f, "Time travel is forbidden outside of Hogwarts"),
+            RepTestError::CheckFailed { run } => {
+                write!(f, "run #{} didn't pass validity check", run)
+            }
+            RepTestError::ReInit => write!(f, "Failed to re-init uncleared RepTester"),
+            RepTestError::NotTesting => write!(f, "RepTester is not testing"),
+            RepTestError::NoAffinity => write!(f, "affinity mask is not readable"),
+            RepTestError::PinFailed { thread } => {
+                write!(f, "worker {} can't be pinned to its core", thread)
+            }
+            RepTestError::NoTimer => write!(f, "timer is not available on a worker"),
+            RepTestError::WorkerPanicked { thread } => {
+                write!(f, "worker {} panicked", thread)
+            }
+            RepTestError::ReportFailed { kind } => write!(f, "report can't be written: {}", kind),
+            RepTestError::SeriesFailed { kind } => write!(f, "series can't be written: {}", kind),
+            RepTestError::BaselineFailed { kind } => {
+                write!(f, "baseline can't be written: {}", kind)
+            }
+            RepTestError::Regressed => write!(f, "slower than the baseline"),
+        }
+    }
+}
+
+impl std::error::Error for RepTestError {}
+
+impl RepTestError {
+    // a regression keeps the exit code CI checks for
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RepTestError::Regressed => REGRESSION_EXIT_CODE,
+            _ => 1,
+        }
+    }
+
+    // for listings, `rep_run!(..).unwrap_or_else(RepTestError::exit)`
+    pub fn exit<T>(self) -> T {
+        eprintln!("{}", self);
+        std::process::exit(self.exit_code())
+    }
+}
+
+#[test]
+fn surfaces_errors_from_finish() {
+    use super::RepTester;
+
+    let mut tester = RepTester::new().unwrap();
+    tester.print = false;
+
+    tester.init("end without start", 1);
+    tester.end_run();
+    assert_eq!(tester.finish(), Err(RepTestError::EndWithoutStart));
+
+    tester.clear();
+    tester.init("check", 1);
+    tester.start_run();
+    tester.end_run();
+    tester.check_failed();
+    assert_eq!(tester.finish(), Err(RepTestError::CheckFailed { run: 1 }));
+
+    tester.clear();
+    tester.init("re-init", 1);
+    tester.init("re-init", 1);
+    assert_eq!(tester.finish(), Err(RepTestError::ReInit));
+
+    tester.clear();
+    tester.start_run();
+    assert_eq!(tester.finish(), Err(RepTestError::NotTesting));
+}
+
+#[test]
+fn surfaces_regressions_from_finish() {
+    use super::{
+        RepTester,
+        baseline::{BaselineMode, BaselineStore},
+        config::RepConfig,
+    };
+
+    let path = std::env::temp_dir().join(format!("rep_test_regressed_{}.json", std::process::id()));
+    let mut tester = RepTester::new()
+        .unwrap()
+        .with_config(RepConfig::default().with_max_runs(5));
+    tester.print = false;
+    let run = |tester: &mut RepTester| {
+        tester.clear();
+        tester.init("regressed", 1);
+        while tester.should_continue() {
+            tester.start_run();
+            std::hint::black_box(0u64);
+            tester.end_run();
+        }
+        tester.finish()
+    };
+
+    tester.set_baseline(BaselineStore::load(&path, BaselineMode::Save).unwrap());
+    assert_eq!(run(&mut tester), Ok(()));
+
+    // any time at all is slower than that
+    let mut store = BaselineStore::load(&path, BaselineMode::Check).unwrap();
+    store.threshold = -1.0;
+    tester.set_baseline(store);
+    let result = run(&mut tester);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(result, Err(RepTestError::Regressed));
+    assert_eq!(RepTestError::Regressed.exit_code(), REGRESSION_EXIT_CODE);
+}
@@ -1,11 +1,45 @@
 use std::{
-    default,
-    io::{self, Stdout, Write, stdout},
-    time::Duration,
+    default, env,
+    fs::File,
+    io::{self, BufWriter, IsTerminal, Stderr, Write, stderr, stdout},
+    time::{Duration, Instant},
     u64,
 };
 
-use crate::{pretty_print_with_options, time::TimeMeasurer};
+use crate::{
+    pretty_print_with_options,
+    time::{
+        frequency::FrequencyMethod,
+        timer::{TIMER_ENV, Timer, TimerKind},
+    },
+};
+
+pub mod baseline;
+pub mod cache_levels;
+pub mod chart;
+pub mod compare;
+pub mod config;
+pub mod error;
+pub mod perf;
+pub mod report;
+pub mod series;
+pub mod stats;
+pub mod sweep;
+pub mod threads;
+pub mod units;
+
+use self::{
+    baseline::BaselineStore,
+    chart::Chart,
+    compare::{Comparison, RunSnapshot},
+    config::{ConfigError, OutputMode, RepConfig, StopReason, StopState},
+    error::RepTestError,
+    perf::{PerfCounters, PerfMeasurement},
+    report::{Reporter, RunRecord, reporter_from_env},
+    series::{SeriesPoint, SeriesWriter, TimeSeries, plot_from_env, series_writer_from_env},
+    stats::{SampleStats, percentile_idx},
+    units::{Prefixes, Unit},
+};
 
 #[derive(Debug)]
 enum Status {
@@ -16,9 +50,17 @@ enum Status {
 }
 
 #[repr(usize)]
+#[derive(Clone, Copy)]
 enum VectorItem {
     Clocks = 1,
     PageFaults,
+    Cycles,
+    Instructions,
+    BranchMisses,
+    L1DMisses,
+    LLCMisses,
+    DTLBMisses,
+    ContextSwitches,
     __CountIdentsLast,
 }
 impl VectorItem {
@@ -40,6 +82,12 @@ struct RepRun {
     min: RunVector,
     max: RunVector,
     avg: RunVectorF64,
+    // Welford's sum of squared clock deviations
+    clocks_m2: f64,
+    samples: Vec<RunVector>,
+    // clocks at init, series timestamps are relative to it
+    origin: u64,
+    series: Vec<SeriesPoint>,
 }
 impl RepRun {
     const MIN_DEFAULT: u64 = u64::MAX;
@@ -79,6 +127,10 @@ impl RepRun {
             avg: [RepRun::AVG_DEFAULT; VEC_SIZE],
             max: [RepRun::ZERO; VEC_SIZE],
             min: [RepRun::MIN_DEFAULT; VEC_SIZE],
+            clocks_m2: 0.0,
+            samples: Vec::new(),
+            origin: 0,
+            series: Vec::new(),
         }
     }
 
@@ -93,130 +145,331 @@ impl RepRun {
         self.avg.fill(RepRun::AVG_DEFAULT);
         self.max.fill(RepRun::ZERO);
         self.min.fill(RepRun::MIN_DEFAULT);
+        self.clocks_m2 = 0.0;
+        self.samples.clear();
+        self.origin = 0;
+        self.series.clear();
+    }
+
+    fn sorted_samples(&self) -> Vec<RunVector> {
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable_by_key(|it| it[VectorItem::Clocks.value()]);
+        sorted
+    }
+
+    fn relative_std_dev(&self) -> f64 {
+        let mean = self.avg[VectorItem::Clocks.value()];
+        if self.runs < 2 || mean == 0.0 {
+            return f64::INFINITY;
+        }
+
+        (self.clocks_m2 / (self.runs - 1) as f64).sqrt() / mean
     }
 }
 pub struct RepTester {
     status: Status,
-    error_message: Option<&'static str>,
-    measurer: TimeMeasurer,
+    error: Option<RepTestError>,
+    timer: Box<dyn Timer>,
+    perf: PerfCounters,
 
     is_running: bool,
     try_before: u64,
     timeout: u64,
+    budget_until: Option<u64>,
+    // end of `config.budget`, fixed by the first init and kept by `clear`
+    budget_deadline: Option<Instant>,
+    stop_reason: Option<StopReason>,
     timer_frequency: u64,
+    frequency_method: Option<FrequencyMethod>,
+    // min clocks of an empty start_run/end_run pair, measured on the first init
+    overhead: Option<u64>,
+    // `config.precise_reads` the overhead was calibrated with
+    overhead_precise: bool,
     counter: u32,
 
     run: RepRun,
+    reporter: Option<Box<dyn Reporter>>,
+    series_out: Option<SeriesWriter<BufWriter<File>>>,
+    baseline: Option<BaselineStore>,
     pub print: bool,
+    pub retain_samples: bool,
+    // timestamped clocks and faults of every run, see `series`
+    pub record_series: bool,
+    pub plot_series: bool,
+    // what `len` counts, `rep_run!(.., unit = Unit::Iterations, ..)` sets it for a single test
+    pub unit: Unit,
+    pub prefixes: Prefixes,
+    pub config: RepConfig,
+    pub output: OutputMode,
+    // in plain mode, keep the best run so far on stderr
+    pub progress: bool,
 }
 
 pub enum MeasurementKind {
     Best,
     Worst,
     Avg,
+    // requires `retain_samples`
+    Median,
+    P90,
+    P99,
 }
 
-const EMPTY: &'static str = "";
+pub const PERF_ENV: &str = "REP_TEST_PERF";
 
 impl RepTester {
     const INIT: u64 = 0;
+    const CALIBRATION_RUNS: u32 = 1000;
+
+    // every `REP_TEST_*` variable is read here, a malformed one is returned instead of ignored
+    pub fn new() -> Result<RepTester, ConfigError> {
+        let kind = TimerKind::from_env();
+        let timer = kind.create().ok_or_else(|| ConfigError {
+            name: TIMER_ENV,
+            message: format!("timer '{}' is not available", kind.name()),
+        })?;
+        let series_out = series_writer_from_env()?;
+        let plot_series = plot_from_env();
 
-    pub fn new() -> Option<RepTester> {
-        TimeMeasurer::init().map(|measurer| RepTester {
+        Ok(RepTester {
             status: Status::Uninit,
-            error_message: None,
-            measurer,
+            error: None,
+            timer,
+            perf: match env::var_os(PERF_ENV) {
+                Some(_) => PerfCounters::open(),
+                None => PerfCounters::disabled(),
+            },
             is_running: false,
             counter: 0,
             run: RepRun::empty(),
             timeout: RepTester::INIT,
+            budget_until: None,
+            budget_deadline: None,
+            stop_reason: None,
             timer_frequency: RepTester::INIT,
+            frequency_method: None,
+            overhead: None,
+            overhead_precise: false,
             try_before: RepTester::INIT,
+            reporter: reporter_from_env()?,
+            record_series: series_out.is_some() || plot_series,
+            series_out,
+            plot_series,
+            unit: Unit::default(),
+            prefixes: Prefixes::from_env()?,
+            baseline: BaselineStore::from_env()?,
             print: true,
+            retain_samples: false,
+            config: RepConfig::from_env()?,
+            output: OutputMode::detect()?,
+            progress: OutputMode::progress_from_env(),
         })
     }
+
+    pub fn with_config(mut self, config: RepConfig) -> RepTester {
+        self.config = config;
+        self
+    }
+
+    // overhead is calibrated again for the new timer on the next init
+    pub fn with_timer(mut self, timer: Box<dyn Timer>) -> RepTester {
+        self.timer = timer;
+        self.overhead = None;
+        self
+    }
+
+    pub fn timer_kind(&self) -> TimerKind {
+        self.timer.kind()
+    }
+    // returns false when neither hardware nor software events are permitted
+    pub fn enable_perf_counters(&mut self) -> bool {
+        self.perf = PerfCounters::open();
+        // reading the counters is part of the measured window
+        self.overhead = None;
+        self.perf.is_enabled()
+    }
+
     #[inline]
-    pub fn error(&mut self, err: &'static str) {
+    pub fn error(&mut self, err: RepTestError) {
         match self.status {
             Status::Errored => {}
             _ => {
                 self.status = Status::Errored;
-                self.error_message = Some(err);
+                self.error = Some(err);
             }
         }
     }
 
-    pub fn init(&mut self, name: &str, bytes: u64, timeout_sec: f64) {
-        let freq = self
-            .measurer
-            .detect_clock_frequency(Duration::from_millis(100));
+    #[inline]
+    pub fn check_failed(&mut self) {
+        self.error(RepTestError::CheckFailed { run: self.run.runs });
+    }
+
+    pub fn init(&mut self, name: &str, bytes: u64) {
+        let frequency = self.timer.frequency(Duration::from_millis(100));
+        let freq = frequency.hz;
         match self.status {
             Status::Uninit => {
+                if self.overhead.is_none() || self.overhead_precise != self.config.precise_reads {
+                    self.overhead_precise = self.config.precise_reads;
+                    self.overhead = Some(self.calibrate());
+                }
+                self.perf.reset();
                 self.run.name = Some(name.to_owned());
                 self.run.bytes = bytes;
                 self.status = Status::Testing;
-                self.error_message = None;
-                self.timeout = (freq as f64 * timeout_sec) as u64;
+                self.error = None;
+                self.timeout = (freq as f64 * self.config.timeout.as_secs_f64()) as u64;
                 self.timer_frequency = freq;
+                self.frequency_method = Some(frequency.method);
 
-                self.try_before = self.measurer.clocks_now() + self.timeout;
+                let now = self.timer.now();
+                self.run.origin = now;
+                self.try_before = now + self.timeout;
+                if self.budget_deadline.is_none() {
+                    self.budget_deadline = self.config.budget.map(|it| Instant::now() + it);
+                }
+                // what is left of the budget, once it's gone every test gets a single run
+                self.budget_until = self.budget_deadline.map(|deadline| {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    now + (freq as f64 * left.as_secs_f64()) as u64
+                });
             }
             _ => {
-                self.status = Status::Errored;
-                self.error_message = Some("Failed to re-init uncleared RepTester");
+                self.error(RepTestError::ReInit);
             }
         }
     }
+
+    // same reads as a real run, with nothing in between
+    fn calibrate(&mut self) -> u64 {
+        let mut overhead = u64::MAX;
+        for _ in 0..RepTester::CALIBRATION_RUNS {
+            let start = self.read_start();
+            let end = self.read_end();
+            let clocks =
+                end[VectorItem::Clocks.value()].saturating_sub(start[VectorItem::Clocks.value()]);
+            overhead = overhead.min(clocks);
+        }
+
+        overhead
+    }
+
+    pub fn overhead_clocks(&self) -> u64 {
+        self.overhead.unwrap_or(0)
+    }
+
+    #[inline(always)]
+    fn read_start(&mut self) -> RunVector {
+        let mut start: RunVector = [0; VEC_SIZE];
+        // the timer is the last thing before the block, the syscalls stay out of its window
+        start[VectorItem::PageFaults.value()] = page_faults();
+        self.perf.read(&mut start);
+        start[VectorItem::Clocks.value()] = if self.config.precise_reads {
+            self.timer.start()
+        } else {
+            self.timer.now()
+        };
+        start
+    }
+
+    #[inline(always)]
+    fn read_end(&mut self) -> RunVector {
+        let mut end: RunVector = [0; VEC_SIZE];
+        end[VectorItem::Clocks.value()] = if self.config.precise_reads {
+            self.timer.end()
+        } else {
+            self.timer.now()
+        };
+        self.perf.read(&mut end);
+        end[VectorItem::PageFaults.value()] = page_faults();
+        end
+    }
+
     pub fn should_continue(&mut self) -> bool {
         match self.status {
-            Status::Testing if self.measurer.clocks_now() < self.try_before => true,
             Status::Testing => {
-                self.status = Status::Finished;
-                false
+                let state = StopState {
+                    runs: self.run.runs,
+                    now: self.timer.now(),
+                    try_before: self.try_before,
+                    budget_until: self.budget_until,
+                    relative_std_dev: self.run.relative_std_dev(),
+                };
+                match self.config.stop_reason(&state) {
+                    None => true,
+                    reason => {
+                        self.stop_reason = reason;
+                        self.status = Status::Finished;
+                        false
+                    }
+                }
             }
             _ => false,
         }
     }
 
+    // for callers which repeat tests, `config.budget` is for all of them together
+    pub fn budget_exhausted(&self) -> bool {
+        self.budget_deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.stop_reason
+    }
+
     pub fn start_run(&mut self) {
         match self.status {
             Status::Testing if self.is_running => {
-                self.status = Status::Errored;
-                self.error_message = Some("Double start have occured");
+                self.error(RepTestError::DoubleStart);
             }
             Status::Testing => {
                 self.is_running = true;
-                self.run.start[VectorItem::Clocks.value()] = self.measurer.clocks_now();
-                self.run.start[VectorItem::PageFaults.value()] = page_faults();
+                self.run.start = self.read_start();
             }
             _ => {
-                self.status = Status::Errored;
-                self.error_message = Some("Invalid start_run");
+                self.error(RepTestError::NotTesting);
             }
         }
     }
 
     pub fn end_run(&mut self) {
-        let now = self.measurer.clocks_now();
-        let page_faults = page_faults();
+        let end = self.read_end();
+        let now = end[VectorItem::Clocks.value()];
 
         match self.status {
             Status::Testing if !self.is_running => {
-                self.error("Invalid end_run command");
+                self.error(RepTestError::EndWithoutStart);
             }
             Status::Testing if now <= self.run.start[VectorItem::Clocks.value()] => {
-                self.error("Time travel is forbidden outside of Hogwarts");
+                self.error(RepTestError::TimeTravel);
             }
             Status::Testing => {
                 self.is_running = false;
                 let total = self.run.runs + 1;
                 self.run.runs = total;
 
-                let current_vec: RunVector = [
-                    0,
-                    now - self.run.start[VectorItem::Clocks.value()],
-                    page_faults - self.run.start[VectorItem::PageFaults.value()],
-                ];
+                let mut current_vec: RunVector = [0; VEC_SIZE];
+                for i in 1..VEC_SIZE {
+                    current_vec[i] = end[i].wrapping_sub(self.run.start[i]);
+                }
+
+                if self.retain_samples {
+                    self.run.samples.push(current_vec);
+                }
+                if self.record_series {
+                    self.run.series.push(SeriesPoint {
+                        at: self.run.start[VectorItem::Clocks.value()] - self.run.origin,
+                        clocks: current_vec[VectorItem::Clocks.value()],
+                        page_faults: current_vec[VectorItem::PageFaults.value()],
+                    });
+                }
+
+                let clocks = current_vec[VectorItem::Clocks.value()] as f64;
+                let prev_mean = self.run.avg[VectorItem::Clocks.value()];
+                let mean = prev_mean + (clocks - prev_mean) / total as f64;
+                self.run.clocks_m2 += (clocks - prev_mean) * (clocks - mean);
 
                 for i in 0..current_vec.len() {
                     self.run.avg[i] = (self.run.avg[i] * (total - 1) as f64
@@ -237,26 +490,176 @@ impl RepTester {
                 }
             }
             _ => {
-                self.error("Invalid end_run");
+                self.error(RepTestError::NotTesting);
             }
         }
     }
 
     pub fn measurement(&self, kind: MeasurementKind) -> PerformanceMeasurement {
         match kind {
-            MeasurementKind::Avg => {
-                PerformanceMeasurement::new(self.run.avg, self.timer_frequency, self.run.bytes)
+            MeasurementKind::Avg => self.measurement_of(self.run.avg),
+            MeasurementKind::Best => self.measurement_of(to_run_vector_f64(&self.run.min)),
+            MeasurementKind::Worst => self.measurement_of(to_run_vector_f64(&self.run.max)),
+            MeasurementKind::Median => self.percentile_measurement(0.5),
+            MeasurementKind::P90 => self.percentile_measurement(0.9),
+            MeasurementKind::P99 => self.percentile_measurement(0.99),
+        }
+    }
+
+    fn percentile_measurement(&self, p: f64) -> PerformanceMeasurement {
+        if self.run.samples.is_empty() {
+            return PerformanceMeasurement::nil();
+        }
+        let sorted = self.run.sorted_samples();
+
+        self.measurement_of(to_run_vector_f64(&sorted[percentile_idx(sorted.len(), p)]))
+    }
+
+    fn measurement_of(&self, mut counts: RunVectorF64) -> PerformanceMeasurement {
+        let clocks = &mut counts[VectorItem::Clocks.value()];
+        if self.config.subtract_overhead && *clocks != 0.0 {
+            // a block cheaper than the calibration still took some time
+            *clocks = (*clocks - self.overhead_clocks() as f64).max(1.0);
+        }
+
+        let mut measurement =
+            PerformanceMeasurement::new(counts, self.timer_frequency, self.run.bytes);
+        if measurement.bytes != 0 {
+            measurement.perf = self.perf.measurement(&counts);
+        }
+        measurement.unit = self.unit;
+        measurement.prefixes = self.prefixes;
+
+        measurement
+    }
+
+    pub fn sample_stats(&self) -> Option<SampleStats> {
+        let clocks: Vec<u64> = self
+            .run
+            .sorted_samples()
+            .iter()
+            .map(|it| it[VectorItem::Clocks.value()])
+            .collect();
+
+        SampleStats::of(&clocks)
+    }
+
+    // requires `record_series`
+    pub fn series(&self) -> Option<TimeSeries> {
+        match self.status {
+            Status::Finished if !self.run.series.is_empty() => Some(TimeSeries {
+                name: self.run.name.clone().expect("must have a name"),
+                timer_frequency: self.timer_frequency,
+                points: self.run.series.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn set_reporter(&mut self, reporter: impl Reporter + 'static) {
+        self.reporter = Some(Box::new(reporter));
+    }
+
+    pub fn record(&self) -> Option<RunRecord<'_>> {
+        match self.status {
+            Status::Finished => Some(RunRecord {
+                name: self.run.name.as_deref().expect("must have a name"),
+                bytes: self.run.bytes,
+                unit: self.unit,
+                runs: self.run.runs,
+                best: self.measurement(MeasurementKind::Best),
+                worst: self.measurement(MeasurementKind::Worst),
+                avg: self.measurement(MeasurementKind::Avg),
+                overhead_clocks: self.overhead_clocks(),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn snapshot(&self) -> Option<RunSnapshot> {
+        match self.status {
+            Status::Finished => Some(RunSnapshot {
+                name: self.run.name.clone().expect("must have a name"),
+                bytes: self.run.bytes,
+                best: self.measurement(MeasurementKind::Best),
+                clocks: self
+                    .run
+                    .samples
+                    .iter()
+                    .map(|it| it[VectorItem::Clocks.value()])
+                    .collect(),
+            }),
+            _ => None,
+        }
+    }
+
+    // compares the just finished run (as variant) against an earlier snapshot
+    pub fn compare(&self, baseline: &RunSnapshot) -> Option<Comparison> {
+        self.snapshot()
+            .map(|variant| Comparison::of(baseline, &variant))
+    }
+
+    pub fn set_baseline(&mut self, store: BaselineStore) {
+        self.baseline = Some(store);
+    }
+
+    pub fn baseline(&self) -> Option<&BaselineStore> {
+        self.baseline.as_ref()
+    }
+
+    // every output is written even when an earlier one fails, the first error is returned
+    pub fn report(&mut self) -> Result<(), RepTestError> {
+        let mut first_error: Option<RepTestError> = None;
+
+        if let Some(mut reporter) = self.reporter.take() {
+            if let Some(record) = self.record()
+                && let Err(error) = reporter.report(&record)
+            {
+                first_error.get_or_insert(RepTestError::ReportFailed { kind: error.kind() });
             }
-            MeasurementKind::Best => PerformanceMeasurement::new(
-                to_run_vector_f64(&self.run.min),
-                self.timer_frequency,
-                self.run.bytes,
-            ),
-            MeasurementKind::Worst => PerformanceMeasurement::new(
-                to_run_vector_f64(&self.run.max),
-                self.timer_frequency,
-                self.run.bytes,
-            ),
+            self.reporter = Some(reporter);
+        }
+
+        if let Some(mut out) = self.series_out.take() {
+            if let Some(series) = self.series()
+                && let Err(error) = out.write(&series)
+            {
+                first_error.get_or_insert(RepTestError::SeriesFailed { kind: error.kind() });
+            }
+            self.series_out = Some(out);
+        }
+
+        if let Some(mut baseline) = self.baseline.take() {
+            if let Some(record) = self.record() {
+                match baseline.process(&record, self.timer_frequency) {
+                    Ok(true) => {
+                        eprintln!("{}", baseline.regressions().last().unwrap());
+                        first_error.get_or_insert(RepTestError::Regressed);
+                    }
+                    Ok(false) => {}
+                    Err(error) => {
+                        first_error
+                            .get_or_insert(RepTestError::BaselineFailed { kind: error.kind() });
+                    }
+                }
+            }
+            self.baseline = Some(baseline);
+        }
+
+        first_error.map_or(Ok(()), Err)
+    }
+
+    // final print and report of a test, errors are surfaced instead of printed only,
+    // one of the test itself comes before those of writing its results
+    pub fn finish(&mut self) -> Result<(), RepTestError> {
+        if self.print {
+            self.print();
+        }
+        let reported = self.report();
+
+        match self.status {
+            Status::Errored => Err(self.error.unwrap_or(RepTestError::NotTesting)),
+            _ => reported,
         }
     }
 
@@ -266,32 +669,89 @@ impl RepTester {
                 let mut out = stdout();
                 let name = self.run.name.as_ref().expect("must have a name");
 
-                if self.counter == 0 {
-                    print_header(&mut out, name).unwrap();
-                    self.counter = 1;
-                } else {
-                    out.write("\x1b[1A\x1b[2K".as_bytes()).unwrap();
+                match self.output {
+                    OutputMode::Interactive if self.counter != 0 => {
+                        out.write("\x1b[1A\x1b[2K".as_bytes()).unwrap();
+                    }
+                    OutputMode::Interactive => {
+                        print_header(&mut out, name).unwrap();
+                        self.counter = 1;
+                    }
+                    OutputMode::Plain => {
+                        if self.progress && self.counter != 0 {
+                            end_progress(&mut stderr()).unwrap();
+                        }
+                        print_header(&mut out, name).unwrap();
+                    }
                 }
 
                 write!(
                     out,
-                    "The best run: {}\nThe worst run: {}\nAverage: {}\n\n",
+                    "The best run: {}\nThe worst run: {}\nAverage: {}\n",
                     self.measurement(MeasurementKind::Best).to_string(),
                     self.measurement(MeasurementKind::Worst).to_string(),
                     self.measurement(MeasurementKind::Avg).to_string(),
                 )
                 .unwrap();
+                writeln!(
+                    out,
+                    "Timer: {}{} at {}Hz ({}); overhead: {} clocks{}\n",
+                    self.timer.kind().name(),
+                    if self.config.precise_reads {
+                        " serialized"
+                    } else {
+                        ""
+                    },
+                    pretty_print_with_options(self.timer_frequency as f64, 0),
+                    self.frequency_method.map_or("unknown", |it| it.name()),
+                    self.overhead_clocks(),
+                    if self.config.subtract_overhead {
+                        " (subtracted)"
+                    } else {
+                        ""
+                    }
+                )
+                .unwrap();
+                if let Some(stats) = self.sample_stats() {
+                    write!(
+                        out,
+                        "Median: {}\nP90: {}\nP99: {}\nStd dev: {} ({:.2}%)\n{}\n",
+                        self.measurement(MeasurementKind::Median).to_string(),
+                        self.measurement(MeasurementKind::P90).to_string(),
+                        self.measurement(MeasurementKind::P99).to_string(),
+                        pretty_print_with_options(stats.std_dev, 3),
+                        stats.relative_std_dev() * 100.0,
+                        stats.histogram.to_string(40),
+                    )
+                    .unwrap();
+                }
+                if self.plot_series
+                    && let Some(series) = self.series()
+                {
+                    series.plot(&mut out, &Chart::default()).unwrap();
+                    writeln!(out).unwrap();
+                }
                 out.flush().unwrap();
             }
-            Status::Errored => {
-                println!(
-                    "Tester errored with {}",
-                    self.error_message.unwrap_or(EMPTY)
-                );
-            }
+            Status::Errored => match self.error {
+                Some(err) => println!("Tester errored with {}", err),
+                None => println!("Tester errored"),
+            },
             Status::Testing if self.counter % 10 != 0 => {
                 self.counter += 1;
             }
+            Status::Testing if self.output == OutputMode::Plain => {
+                self.counter += 1;
+                if self.progress {
+                    let name = self.run.name.as_ref().expect("must have a name");
+                    write_progress(
+                        &mut stderr(),
+                        name,
+                        &self.measurement(MeasurementKind::Best).to_string(),
+                    )
+                    .unwrap();
+                }
+            }
             Status::Testing => {
                 self.counter += 1;
                 let mut out = stdout();
@@ -317,35 +777,68 @@ impl RepTester {
     }
     pub fn clear(&mut self) {
         self.status = Status::Uninit;
-        self.error_message = None;
+        self.error = None;
 
         self.run.clear();
 
         self.is_running = false;
         self.counter = 0;
         self.timeout = RepTester::INIT;
+        self.budget_until = None;
+        self.stop_reason = None;
         self.timer_frequency = RepTester::INIT;
+        self.frequency_method = None;
         self.try_before = RepTester::INIT;
     }
 }
 
-fn print_header(out: &mut Stdout, name: &str) -> io::Result<()> {
+fn print_header(out: &mut impl Write, name: &str) -> io::Result<()> {
     writeln!(out, "--- {} ---", name)
 }
 
+fn write_progress(err: &mut Stderr, name: &str, best: &str) -> io::Result<()> {
+    if err.is_terminal() {
+        write!(err, "\r\x1b[2K{}: {}", name, best)?;
+    } else {
+        writeln!(err, "{}: {}", name, best)?;
+    }
+    err.flush()
+}
+
+fn end_progress(err: &mut Stderr) -> io::Result<()> {
+    if err.is_terminal() {
+        write!(err, "\r\x1b[2K")?;
+    }
+    err.flush()
+}
+
 fn to_run_vector_f64(vector: &RunVector) -> RunVectorF64 {
     vector.map(|it| it as f64)
 }
 
 #[derive(Default, Clone, Copy)]
 pub struct PerformanceMeasurement {
+    // amount of `unit` processed by a run, bytes unless the test says otherwise
     pub bytes: u64,
     pub time: f64,
     pub faults: f64,
     pub clocks: f64,
+    pub perf: PerfMeasurement,
+    pub unit: Unit,
+    pub prefixes: Prefixes,
 }
 
 impl PerformanceMeasurement {
+    #[inline]
+    // `unit` per second
+    pub fn rate(&self) -> f64 {
+        if self.time == 0.0 {
+            return 0.0;
+        }
+
+        self.bytes as f64 / self.time
+    }
+
     #[inline]
     pub fn throughput_mb(&self) -> f64 {
         if self.time == 0.0 {
@@ -364,37 +857,57 @@ impl PerformanceMeasurement {
 
         let faults = self.faults;
         let page_faults = if faults > 0.0 {
-            let mut pf_per_byte = bytes as f64 / faults;
-            let unit = if pf_per_byte > 1024.0 * 1024.0 {
-                pf_per_byte /= 1024.0 * 1024.0;
-                "m"
-            } else if pf_per_byte > 1024.0 {
-                pf_per_byte /= 1024.0;
-                "k"
-            } else {
-                "b"
-            };
-
             format!(
-                "; PF={} ({}{}/fault)",
+                "; PF={} ({}/fault)",
                 pretty_print_with_options(faults, 3),
-                pretty_print_with_options(pf_per_byte, 3),
-                unit
+                self.prefixes.format(bytes as f64 / faults, self.unit)
             )
         } else {
             String::new()
         };
-        let throughput = (bytes as f64 / self.time) / (1024.0 * 1024.0);
 
         format!(
-            "{}({:.2} ms) {:.3} mb/s{}",
+            "{}({:.2} ms) {}{}{}",
             pretty_print_with_options(clocks, 3),
             self.time * 1000.0,
-            throughput,
-            page_faults
+            self.rate_string(),
+            page_faults,
+            self.perf.to_string(bytes, self.unit)
         )
     }
 
+    // core cycles when perf counters are on, timer clocks otherwise
+    fn cycles(&self) -> (f64, &'static str) {
+        match self.perf.cycles {
+            Some(cycles) if cycles > 0.0 => (cycles, "cycles"),
+            _ => (self.clocks, "clocks"),
+        }
+    }
+
+    fn rate_string(&self) -> String {
+        let amount = self.bytes as f64;
+        let rate = self.prefixes.format(amount / self.time, self.unit);
+        let (cycles, cycles_name) = self.cycles();
+
+        match self.unit {
+            Unit::Bytes => format!("{}/s", rate),
+            Unit::Iterations | Unit::Elements => format!(
+                "{:.3} {}/{}; {}/s",
+                cycles / amount,
+                cycles_name,
+                self.unit.name(),
+                rate
+            ),
+            Unit::Instructions => format!(
+                "{:.3} {}/{}; {}/s",
+                amount / cycles,
+                self.unit.name(),
+                cycles_name.trim_end_matches('s'),
+                rate
+            ),
+        }
+    }
+
     fn new(counts: RunVectorF64, timer_frequency: u64, bytes: u64) -> PerformanceMeasurement {
         let clocks = counts[VectorItem::Clocks.value()];
         if bytes == 0 || clocks == 0.0 {
@@ -409,6 +922,9 @@ impl PerformanceMeasurement {
             time,
             faults: page_faults,
             clocks,
+            perf: PerfMeasurement::default(),
+            unit: Unit::default(),
+            prefixes: Prefixes::default(),
         };
     }
     fn nil() -> PerformanceMeasurement {
@@ -417,20 +933,20 @@ impl PerformanceMeasurement {
             time: 0.0,
             faults: 0.0,
             clocks: 0.0,
+            perf: PerfMeasurement::default(),
+            unit: Unit::default(),
+            prefixes: Prefixes::default(),
         }
     }
 }
 
+// evaluates to the `RepTestError` of `finish`, callers decide whether it's fatal
 #[macro_export]
 macro_rules! rep_run {
     ($rep_tester: expr, name = $name:expr, len=$len:expr, before = {$($before:tt)*}, block = {$($block:tt)*}, check = {$check:expr}, after_run={$($after:tt)*}) => {{
         let len = $len;
-        #[cfg(feature = "precise_rep_test")]
-        const TIMEOUT: f64 = 10.0;
-        #[cfg(not(feature = "precise_rep_test"))]
-        const TIMEOUT: f64 = 3.0;
         $rep_tester.clear();
-        $rep_tester.init($name, len as u64, TIMEOUT);
+        $rep_tester.init($name, len as u64);
 
         while $rep_tester.should_continue() {
             $($before)*
@@ -439,7 +955,7 @@ macro_rules! rep_run {
             $rep_tester.end_run();
 
             if !$check {
-                $rep_tester.error("didn't pass validity check");
+                $rep_tester.check_failed();
             }
 
             if $rep_tester.print {
@@ -449,26 +965,173 @@ macro_rules! rep_run {
             $($after)*
         }
 
-        if $rep_tester.print {
-            $rep_tester.print();
-        }
+        $rep_tester.finish()
+    }};
+
+    ($rep_tester: expr, name = $name:expr, len=$len:expr, unit = $unit:expr, $($rest:tt)*) => {{
+        let unit = std::mem::replace(&mut $rep_tester.unit, $unit);
+        let result = rep_run!($rep_tester, name = $name, len = $len, $($rest)*);
+        $rep_tester.unit = unit;
+        result
     }};
 
     ($rep_tester: expr, name = $name:expr, len=$len:expr, block = {$($block:tt)*} $(,)?) => {
         rep_run!(
             $rep_tester, name = $name, len=$len, before = {}, block = {$($block)*}, check = {true}, after_run = {}
-        );
+        )
     };
 
     ($rep_tester: expr, name = $name:expr, len=$len:expr, before = {$($before:tt)*}, block = {$($block:tt)*} $(,)?) => {
         rep_run!(
             $rep_tester, name = $name, len=$len, before = {$($before)*}, block = {$($block)*}, check = {true}, after_run = {}
-        );
+        )
     };
 
     ($rep_tester: expr, name = $name:expr, len=$len:expr, before = {$($before:tt)*}, block = {$($block:tt)*}, check = {$check:expr}$(,)?) => {
         rep_run!(
             $rep_tester, name = $name, len=$len, before = {$($before)*}, block = {$($block)*}, check = {$check}, after_run = {}
-        );
+        )
+    }
+}
+
+#[test]
+fn subtracts_calibrated_overhead() {
+    let config = RepConfig::default().with_max_runs(100);
+    let mut tester = RepTester::new().unwrap().with_config(config);
+    tester.print = false;
+
+    tester.init("empty", 1);
+    while tester.should_continue() {
+        tester.start_run();
+        tester.end_run();
     }
+    tester.finish().unwrap();
+
+    let overhead = tester.overhead_clocks();
+    assert!(overhead > 0);
+    let raw = tester.measurement(MeasurementKind::Best).clocks;
+    assert!(raw >= overhead as f64);
+
+    tester.config.subtract_overhead = true;
+    let subtracted = tester.measurement(MeasurementKind::Best).clocks;
+    assert_eq!(subtracted, (raw - overhead as f64).max(1.0));
+}
+
+#[test]
+fn recalibrates_overhead_for_precise_reads() {
+    let config = RepConfig::default().with_max_runs(10);
+    let mut tester = RepTester::new().unwrap().with_config(config);
+    tester.print = false;
+
+    for precise in [false, true] {
+        tester.config.precise_reads = precise;
+        tester.clear();
+        tester.init("empty", 1);
+        assert_eq!(tester.overhead_precise, precise);
+        while tester.should_continue() {
+            tester.start_run();
+            tester.end_run();
+        }
+        tester.finish().unwrap();
+        assert_eq!(tester.run.runs, 10);
+    }
+}
+
+#[test]
+fn measures_with_selected_timer() {
+    let timer = TimerKind::Monotonic.create().unwrap();
+    let config = RepConfig::default().with_max_runs(10);
+    let mut tester = RepTester::new()
+        .unwrap()
+        .with_config(config)
+        .with_timer(timer);
+    tester.print = false;
+    assert_eq!(tester.timer_kind(), TimerKind::Monotonic);
+
+    tester.init("sleep", 1);
+    while tester.should_continue() {
+        tester.start_run();
+        std::thread::sleep(Duration::from_millis(1));
+        tester.end_run();
+    }
+    tester.finish().unwrap();
+
+    // nanoseconds
+    assert!(tester.measurement(MeasurementKind::Best).clocks >= 1_000_000.0);
+}
+
+#[test]
+fn formats_declared_units() {
+    let measurement = |unit: Unit, prefixes: Prefixes| PerformanceMeasurement {
+        bytes: 1_000_000,
+        time: 0.001,
+        clocks: 3_000_000.0,
+        unit,
+        prefixes,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        measurement(Unit::Bytes, Prefixes::Si).to_string(),
+        "3_000_000(1.00 ms) 1.000 GB/s"
+    );
+    assert_eq!(
+        measurement(Unit::Bytes, Prefixes::Iec).to_string(),
+        "3_000_000(1.00 ms) 953.674 MiB/s"
+    );
+    assert_eq!(
+        measurement(Unit::Iterations, Prefixes::Iec).to_string(),
+        "3_000_000(1.00 ms) 3.000 clocks/iter; 1.000 Giter/s"
+    );
+    assert_eq!(
+        measurement(Unit::Instructions, Prefixes::Si).to_string(),
+        "3_000_000(1.00 ms) 0.333 ops/clock; 1.000 Gops/s"
+    );
+}
+
+#[test]
+fn rep_run_restores_unit() {
+    let config = RepConfig::default().with_max_runs(3);
+    let mut tester = RepTester::new().unwrap().with_config(config);
+    tester.print = false;
+
+    let mut unit = None;
+    crate::rep_run!(
+        tester,
+        name = "loop",
+        len = 10,
+        unit = Unit::Iterations,
+        block = {
+            unit = Some(tester.unit);
+        }
+    )
+    .unwrap();
+
+    assert_eq!(unit, Some(Unit::Iterations));
+    assert_eq!(tester.unit, Unit::Bytes);
+}
+
+#[test]
+fn budget_covers_all_tests() {
+    let config = RepConfig::default()
+        .with_timeout(Duration::from_secs(10))
+        .with_budget(Duration::from_millis(50));
+    let mut tester = RepTester::new().unwrap().with_config(config);
+    tester.print = false;
+
+    for name in ["first", "second"] {
+        tester.clear();
+        tester.init(name, 1);
+        while tester.should_continue() {
+            tester.start_run();
+            std::thread::sleep(Duration::from_millis(1));
+            tester.end_run();
+        }
+        tester.finish().unwrap();
+        assert_eq!(tester.stop_reason(), Some(StopReason::Budget));
+    }
+
+    // the first test used it up, the second one only got a single run
+    assert_eq!(tester.run.runs, 1);
+    assert!(tester.budget_exhausted());
 }
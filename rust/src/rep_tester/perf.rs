@@ -0,0 +1,315 @@
+use super::{RunVector, VectorItem, units::Unit};
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct PerfMeasurement {
+    pub cycles: Option<f64>,
+    pub instructions: Option<f64>,
+    pub branch_misses: Option<f64>,
+    pub l1d_misses: Option<f64>,
+    pub llc_misses: Option<f64>,
+    pub dtlb_misses: Option<f64>,
+    pub context_switches: Option<f64>,
+}
+
+impl PerfMeasurement {
+    pub fn ipc(&self) -> Option<f64> {
+        match (self.instructions, self.cycles) {
+            (Some(instructions), Some(cycles)) if cycles > 0.0 => Some(instructions / cycles),
+            _ => None,
+        }
+    }
+
+    pub fn per_byte(value: Option<f64>, bytes: u64) -> Option<f64> {
+        match value {
+            Some(value) if bytes > 0 => Some(value / bytes as f64),
+            _ => None,
+        }
+    }
+
+    // cache misses are per `unit` of the test
+    pub fn to_string(&self, bytes: u64, unit: Unit) -> String {
+        let mut parts = Vec::with_capacity(6);
+
+        if let Some(ipc) = self.ipc() {
+            parts.push(format!("IPC={:.2}", ipc));
+        }
+        if let (Some(misses), Some(instructions)) = (self.branch_misses, self.instructions)
+            && instructions > 0.0
+        {
+            parts.push(format!(
+                "br-miss={:.3}/1k ins",
+                misses * 1000.0 / instructions
+            ));
+        }
+        for (name, value) in [
+            ("L1D", self.l1d_misses),
+            ("LLC", self.llc_misses),
+            ("dTLB", self.dtlb_misses),
+        ] {
+            if let Some(per_byte) = PerfMeasurement::per_byte(value, bytes) {
+                parts.push(format!("{}={:.4} miss/{}", name, per_byte, unit.name()));
+            }
+        }
+        if let Some(switches) = self.context_switches
+            && switches > 0.0
+        {
+            parts.push(format!("cs={}", switches));
+        }
+
+        if parts.is_empty() {
+            return String::new();
+        }
+        format!("; {}", parts.join(" "))
+    }
+}
+
+const EVENTS: [VectorItem; 7] = [
+    VectorItem::Cycles,
+    VectorItem::Instructions,
+    VectorItem::BranchMisses,
+    VectorItem::L1DMisses,
+    VectorItem::LLCMisses,
+    VectorItem::DTLBMisses,
+    VectorItem::ContextSwitches,
+];
+
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        use std::os::fd::{AsRawFd, OwnedFd};
+
+        use crate::perf_event::{
+            PERF_COUNT_HW_BRANCH_MISSES, PERF_COUNT_HW_CACHE_DTLB, PERF_COUNT_HW_CACHE_L1D,
+            PERF_COUNT_HW_CACHE_LL, PERF_COUNT_HW_CACHE_OP_READ, PERF_COUNT_HW_CACHE_RESULT_MISS,
+            PERF_COUNT_HW_CPU_CYCLES, PERF_COUNT_HW_INSTRUCTIONS, PERF_COUNT_SW_CONTEXT_SWITCHES,
+            PERF_FORMAT_GROUP, PERF_FORMAT_TOTAL_TIME_ENABLED, PERF_FORMAT_TOTAL_TIME_RUNNING,
+            PERF_TYPE_HARDWARE, PERF_TYPE_HW_CACHE, PERF_TYPE_SOFTWARE,
+            open_event,
+        };
+
+        fn event_of(item: &VectorItem) -> (u32, u64) {
+            const fn cache(id: u64) -> u64 {
+                id | (PERF_COUNT_HW_CACHE_OP_READ << 8) | (PERF_COUNT_HW_CACHE_RESULT_MISS << 16)
+            }
+
+            match item {
+                VectorItem::Cycles => (PERF_TYPE_HARDWARE, PERF_COUNT_HW_CPU_CYCLES),
+                VectorItem::Instructions => (PERF_TYPE_HARDWARE, PERF_COUNT_HW_INSTRUCTIONS),
+                VectorItem::BranchMisses => (PERF_TYPE_HARDWARE, PERF_COUNT_HW_BRANCH_MISSES),
+                VectorItem::L1DMisses => (PERF_TYPE_HW_CACHE, cache(PERF_COUNT_HW_CACHE_L1D)),
+                VectorItem::LLCMisses => (PERF_TYPE_HW_CACHE, cache(PERF_COUNT_HW_CACHE_LL)),
+                VectorItem::DTLBMisses => (PERF_TYPE_HW_CACHE, cache(PERF_COUNT_HW_CACHE_DTLB)),
+                VectorItem::ContextSwitches => (PERF_TYPE_SOFTWARE, PERF_COUNT_SW_CONTEXT_SWITCHES),
+                _ => unreachable!("not a perf event"),
+            }
+        }
+
+        const READ_FORMAT: u64 =
+            PERF_FORMAT_GROUP | PERF_FORMAT_TOTAL_TIME_ENABLED | PERF_FORMAT_TOTAL_TIME_RUNNING;
+
+        // groups are scheduled onto the PMU whole, so they stay small enough to fit next to
+        // an NMI watchdog, only IPC needs cycles and instructions counted over the same time
+        const GROUPS: [&[VectorItem]; 6] = [
+            &[VectorItem::Cycles, VectorItem::Instructions],
+            &[VectorItem::BranchMisses],
+            &[VectorItem::L1DMisses],
+            &[VectorItem::LLCMisses],
+            &[VectorItem::DTLBMisses],
+            &[VectorItem::ContextSwitches],
+        ];
+
+        struct Group {
+            // group leader is the first one
+            fds: Vec<OwnedFd>,
+            // vector slot of every opened event, in group read order
+            slots: Vec<usize>,
+            buf: Vec<u64>,
+            // the PMU had no room for the group during a read of this test, its counts are 0
+            unscheduled: bool,
+        }
+
+        impl Group {
+            fn open(items: &[VectorItem]) -> Option<Group> {
+                let mut group = Group {
+                    fds: Vec::new(),
+                    slots: Vec::new(),
+                    buf: Vec::new(),
+                    unscheduled: false,
+                };
+
+                for item in items {
+                    let (kind, config) = event_of(item);
+                    let group_fd = group.fds.first().map_or(-1, |it| it.as_raw_fd());
+
+                    if let Some(fd) = open_event(kind, config, group_fd, READ_FORMAT) {
+                        group.fds.push(fd);
+                        group.slots.push(item.value());
+                    }
+                }
+                if group.fds.is_empty() {
+                    return None;
+                }
+                group.buf = vec![0; group.slots.len() + 3];
+
+                Some(group)
+            }
+
+            #[inline(always)]
+            fn read(&mut self, vector: &mut RunVector) {
+                let bytes = self.buf.len() * size_of::<u64>();
+                let read = unsafe {
+                    libc::read(
+                        self.fds[0].as_raw_fd(),
+                        self.buf.as_mut_ptr() as *mut libc::c_void,
+                        bytes,
+                    )
+                };
+                if read != bytes as isize {
+                    return;
+                }
+
+                // { nr, time_enabled, time_running, values[nr] }
+                let (enabled, running) = (self.buf[1], self.buf[2]);
+                if running == 0 {
+                    self.unscheduled = true;
+                    return;
+                }
+                for (idx, slot) in self.slots.iter().enumerate() {
+                    vector[*slot] = scaled(self.buf[idx + 3], enabled, running);
+                }
+            }
+        }
+
+        pub struct PerfCounters {
+            groups: Vec<Group>,
+        }
+
+        impl PerfCounters {
+            pub fn disabled() -> PerfCounters {
+                PerfCounters { groups: Vec::new() }
+            }
+
+            // events the PMU (or container) refuses are skipped,
+            // with no PMU access only software events remain
+            pub fn open() -> PerfCounters {
+                PerfCounters {
+                    groups: GROUPS.into_iter().filter_map(Group::open).collect(),
+                }
+            }
+
+            #[inline(always)]
+            pub(super) fn read(&mut self, vector: &mut RunVector) {
+                for group in &mut self.groups {
+                    group.read(vector);
+                }
+            }
+
+            // at the start of every test
+            pub(super) fn reset(&mut self) {
+                for group in &mut self.groups {
+                    group.unscheduled = false;
+                }
+            }
+
+            pub(super) fn is_available(&self, item: VectorItem) -> bool {
+                self.groups
+                    .iter()
+                    .any(|it| !it.unscheduled && it.slots.contains(&item.value()))
+            }
+        }
+    } else {
+        pub struct PerfCounters;
+
+        impl PerfCounters {
+            pub fn disabled() -> PerfCounters {
+                PerfCounters
+            }
+            pub fn open() -> PerfCounters {
+                PerfCounters
+            }
+            #[inline(always)]
+            pub(super) fn read(&mut self, _vector: &mut RunVector) {}
+
+            pub(super) fn reset(&mut self) {}
+
+            pub(super) fn is_available(&self, _item: VectorItem) -> bool {
+                false
+            }
+        }
+    }
+}
+
+// with more events than hardware counters the kernel time-shares them,
+// a count seen for part of the time is extrapolated to all of it like `perf stat` does
+#[allow(dead_code)]
+fn scaled(count: u64, time_enabled: u64, time_running: u64) -> u64 {
+    if time_running >= time_enabled {
+        return count;
+    }
+
+    (count as u128 * time_enabled as u128 / time_running as u128) as u64
+}
+
+impl PerfCounters {
+    pub fn is_enabled(&self) -> bool {
+        EVENTS.into_iter().any(|item| self.is_available(item))
+    }
+
+    pub(super) fn measurement(&self, counts: &[f64]) -> PerfMeasurement {
+        let value = |item: VectorItem| {
+            let idx = item.value();
+            self.is_available(item).then(|| counts[idx])
+        };
+
+        PerfMeasurement {
+            cycles: value(VectorItem::Cycles),
+            instructions: value(VectorItem::Instructions),
+            branch_misses: value(VectorItem::BranchMisses),
+            l1d_misses: value(VectorItem::L1DMisses),
+            llc_misses: value(VectorItem::LLCMisses),
+            dtlb_misses: value(VectorItem::DTLBMisses),
+            context_switches: value(VectorItem::ContextSwitches),
+        }
+    }
+}
+
+#[test]
+fn perf_measurement_formatting() {
+    let measurement = PerfMeasurement {
+        cycles: Some(1000.0),
+        instructions: Some(2500.0),
+        branch_misses: Some(5.0),
+        llc_misses: Some(64.0),
+        ..Default::default()
+    };
+
+    assert_eq!(measurement.ipc(), Some(2.5));
+    assert_eq!(
+        measurement.to_string(1024, Unit::Bytes),
+        "; IPC=2.50 br-miss=2.000/1k ins LLC=0.0625 miss/B"
+    );
+    assert_eq!(
+        measurement.to_string(16, Unit::Iterations),
+        "; IPC=2.50 br-miss=2.000/1k ins LLC=4.0000 miss/iter"
+    );
+    assert_eq!(PerfMeasurement::default().to_string(1024, Unit::Bytes), "");
+}
+
+#[test]
+fn open_degrades_gracefully() {
+    let mut counters = PerfCounters::open();
+    let mut vector: RunVector = [0; super::VEC_SIZE];
+    counters.read(&mut vector);
+
+    let measurement = counters.measurement(&vector.map(|it| it as f64));
+    assert_eq!(
+        measurement.cycles.is_some(),
+        counters.is_available(VectorItem::Cycles)
+    );
+}
+
+#[test]
+fn scales_multiplexed_counts() {
+    assert_eq!(scaled(1000, 200, 200), 1000);
+    // counted a quarter of the time
+    assert_eq!(scaled(1000, 400, 100), 4000);
+}
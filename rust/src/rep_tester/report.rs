@@ -0,0 +1,270 @@
+use std::{
+    env,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use super::{PerformanceMeasurement, config::ConfigError, units::Unit};
+
+pub struct RunRecord<'a> {
+    pub name: &'a str,
+    // amount of `unit` per run, the `rate` of measurements is `unit` per second
+    pub bytes: u64,
+    pub unit: Unit,
+    pub runs: u64,
+    pub best: PerformanceMeasurement,
+    pub worst: PerformanceMeasurement,
+    pub avg: PerformanceMeasurement,
+    // calibrated cost of an empty run, already subtracted from measurements if configured
+    pub overhead_clocks: u64,
+}
+
+pub trait Reporter {
+    fn report(&mut self, record: &RunRecord) -> io::Result<()>;
+}
+
+pub struct JsonLinesReporter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> JsonLinesReporter<W> {
+    pub fn new(out: W) -> Self {
+        JsonLinesReporter { out }
+    }
+}
+
+impl JsonLinesReporter<BufWriter<File>> {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(JsonLinesReporter::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> Reporter for JsonLinesReporter<W> {
+    fn report(&mut self, record: &RunRecord) -> io::Result<()> {
+        let out = &mut self.out;
+
+        write!(out, "{{\"name\":")?;
+        write_json_str(out, record.name)?;
+        write!(out, ",\"bytes\":{},\"unit\":", record.bytes)?;
+        write_json_str(out, record.unit.name())?;
+        write!(out, ",\"runs\":{}", record.runs)?;
+        for (key, measurement) in [
+            ("best", &record.best),
+            ("worst", &record.worst),
+            ("avg", &record.avg),
+        ] {
+            write!(
+                out,
+                ",\"{}\":{{\"clocks\":{},\"seconds\":{},\"page_faults\":{},\"rate\":{}",
+                key,
+                json_f64(measurement.clocks),
+                json_f64(measurement.time),
+                json_f64(measurement.faults),
+                json_f64(measurement.rate()),
+            )?;
+            if record.unit == Unit::Bytes {
+                write!(
+                    out,
+                    ",\"throughput_mb\":{}",
+                    json_f64(measurement.throughput_mb())
+                )?;
+            }
+            write!(out, "}}")?;
+        }
+        writeln!(out, ",\"overhead_clocks\":{}}}", record.overhead_clocks)?;
+        out.flush()
+    }
+}
+
+pub struct CsvReporter<W: Write> {
+    out: W,
+    header_written: bool,
+}
+
+impl<W: Write> CsvReporter<W> {
+    pub fn new(out: W) -> Self {
+        CsvReporter {
+            out,
+            header_written: false,
+        }
+    }
+}
+
+impl CsvReporter<BufWriter<File>> {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(CsvReporter::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> Reporter for CsvReporter<W> {
+    fn report(&mut self, record: &RunRecord) -> io::Result<()> {
+        let out = &mut self.out;
+
+        if !self.header_written {
+            write!(out, "name,bytes,unit,runs")?;
+            for key in ["best", "worst", "avg"] {
+                write!(
+                    out,
+                    ",{0}_clocks,{0}_seconds,{0}_page_faults,{0}_rate,{0}_throughput_mb",
+                    key
+                )?;
+            }
+            writeln!(out, ",overhead_clocks")?;
+            self.header_written = true;
+        }
+
+        write_csv_str(out, record.name)?;
+        write!(
+            out,
+            ",{},{},{}",
+            record.bytes,
+            record.unit.name(),
+            record.runs
+        )?;
+        for measurement in [&record.best, &record.worst, &record.avg] {
+            write!(
+                out,
+                ",{},{},{},{},",
+                measurement.clocks,
+                measurement.time,
+                measurement.faults,
+                measurement.rate(),
+            )?;
+            // left empty when the test doesn't count bytes
+            if record.unit == Unit::Bytes {
+                write!(out, "{}", measurement.throughput_mb())?;
+            }
+        }
+        writeln!(out, ",{}", record.overhead_clocks)?;
+        out.flush()
+    }
+}
+
+pub const REPORT_ENV: &str = "REP_TEST_REPORT";
+
+// `REP_TEST_REPORT=out.csv` or `REP_TEST_REPORT=out.jsonl`
+pub fn reporter_from_env() -> Result<Option<Box<dyn Reporter>>, ConfigError> {
+    let Some(path) = env::var_os(REPORT_ENV) else {
+        return Ok(None);
+    };
+    let path = Path::new(&path);
+    let file_error = |it| ConfigError::file(REPORT_ENV, it);
+
+    let reporter: Box<dyn Reporter> = match path.extension().and_then(|it| it.to_str()) {
+        Some("csv") => Box::new(CsvReporter::create(path).map_err(file_error)?),
+        _ => Box::new(JsonLinesReporter::create(path).map_err(file_error)?),
+    };
+
+    Ok(Some(reporter))
+}
+
+fn json_f64(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+pub(crate) fn write_json_str(out: &mut impl Write, value: &str) -> io::Result<()> {
+    write!(out, "\"")?;
+    for char in value.chars() {
+        match char {
+            '"' => write!(out, "\\\"")?,
+            '\\' => write!(out, "\\\\")?,
+            '\n' => write!(out, "\\n")?,
+            '\r' => write!(out, "\\r")?,
+            '\t' => write!(out, "\\t")?,
+            char if (char as u32) < 0x20 => write!(out, "\\u{:04x}", char as u32)?,
+            char => write!(out, "{}", char)?,
+        }
+    }
+    write!(out, "\"")
+}
+
+pub(crate) fn write_csv_str(out: &mut impl Write, value: &str) -> io::Result<()> {
+    if value.contains([',', '"', '\n', '\r']) {
+        write!(out, "\"{}\"", value.replace('"', "\"\""))
+    } else {
+        write!(out, "{}", value)
+    }
+}
+
+#[cfg(test)]
+fn test_record(name: &str) -> RunRecord<'_> {
+    let measurement = PerformanceMeasurement {
+        bytes: 1024 * 1024,
+        time: 0.5,
+        faults: 2.0,
+        clocks: 1000.0,
+        ..Default::default()
+    };
+    RunRecord {
+        name,
+        bytes: 1024 * 1024,
+        unit: Unit::Bytes,
+        runs: 3,
+        best: measurement,
+        worst: measurement,
+        avg: measurement,
+        overhead_clocks: 40,
+    }
+}
+
+#[test]
+fn json_lines_report() {
+    let mut reporter = JsonLinesReporter::new(Vec::new());
+    reporter.report(&test_record("read \"x\"")).unwrap();
+
+    let out = String::from_utf8(reporter.out).unwrap();
+    assert_eq!(
+        out,
+        concat!(
+            "{\"name\":\"read \\\"x\\\"\",\"bytes\":1048576,\"unit\":\"B\",\"runs\":3",
+            ",\"best\":{\"clocks\":1000,\"seconds\":0.5,\"page_faults\":2,\"rate\":2097152,\"throughput_mb\":2}",
+            ",\"worst\":{\"clocks\":1000,\"seconds\":0.5,\"page_faults\":2,\"rate\":2097152,\"throughput_mb\":2}",
+            ",\"avg\":{\"clocks\":1000,\"seconds\":0.5,\"page_faults\":2,\"rate\":2097152,\"throughput_mb\":2}",
+            ",\"overhead_clocks\":40}\n"
+        )
+    );
+}
+
+#[test]
+fn csv_report_writes_header_once() {
+    let mut reporter = CsvReporter::new(Vec::new());
+    reporter.report(&test_record("a,b")).unwrap();
+    reporter.report(&test_record("c")).unwrap();
+
+    let out = String::from_utf8(reporter.out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("name,bytes,unit,runs,best_clocks,"));
+    assert!(lines[1].starts_with("\"a,b\",1048576,B,3,1000,0.5,2,2097152,2,"));
+    assert!(lines[2].starts_with("c,1048576,B,3,"));
+    assert!(lines[0].ends_with(",overhead_clocks"));
+    assert!(lines[2].ends_with(",40"));
+}
+
+#[test]
+fn reports_rate_in_declared_unit() {
+    let mut record = test_record("loop");
+    record.unit = Unit::Iterations;
+
+    let mut json = JsonLinesReporter::new(Vec::new());
+    json.report(&record).unwrap();
+    let json = String::from_utf8(json.out).unwrap();
+    assert!(json.contains("\"unit\":\"iter\""));
+    assert!(json.contains("\"rate\":2097152}"));
+    assert!(!json.contains("throughput_mb"));
+
+    let mut csv = CsvReporter::new(Vec::new());
+    csv.report(&record).unwrap();
+    let csv = String::from_utf8(csv.out).unwrap();
+    assert!(
+        csv.lines()
+            .nth(1)
+            .unwrap()
+            .starts_with("loop,1048576,iter,3,1000,0.5,2,2097152,,")
+    );
+}
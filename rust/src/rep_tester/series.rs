@@ -0,0 +1,190 @@
+use std::{
+    env,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use super::{chart::Chart, config::ConfigError, report::write_csv_str};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SeriesPoint {
+    // clocks since `init`, at the start of the run
+    pub at: u64,
+    pub clocks: u64,
+    pub page_faults: u64,
+}
+
+// every run of a test in order, to see drift the min/avg hide (warm-up, throttling)
+pub struct TimeSeries {
+    pub name: String,
+    pub timer_frequency: u64,
+    pub points: Vec<SeriesPoint>,
+}
+
+impl TimeSeries {
+    pub fn seconds(&self, clocks: u64) -> f64 {
+        clocks as f64 / self.timer_frequency as f64
+    }
+
+    // fastest run per column, so a single slow outlier doesn't hide the trend
+    pub fn plot(&self, out: &mut impl Write, chart: &Chart) -> io::Result<()> {
+        let Some(last) = self.points.last() else {
+            return writeln!(out, "(no runs)");
+        };
+        let buckets = chart.width.max(1) as u64;
+        let span = last.at.max(1);
+
+        let mut mins: Vec<Option<(u64, u64)>> = vec![None; buckets as usize];
+        for point in &self.points {
+            let bucket = ((point.at as u128 * (buckets - 1) as u128) / span as u128) as usize;
+            let min = mins[bucket].get_or_insert((point.at, point.clocks));
+            if point.clocks < min.1 {
+                *min = (point.at, point.clocks);
+            }
+        }
+        let points: Vec<(u64, f64)> = mins
+            .into_iter()
+            .flatten()
+            .map(|(at, clocks)| ((self.seconds(at) * 1000.0) as u64, clocks as f64))
+            .collect();
+
+        writeln!(out, "clocks per run by ms since start of {}", self.name)?;
+        chart.linear().render(out, &points)
+    }
+}
+
+pub struct SeriesWriter<W: Write> {
+    out: W,
+    header_written: bool,
+}
+
+impl<W: Write> SeriesWriter<W> {
+    pub fn new(out: W) -> Self {
+        SeriesWriter {
+            out,
+            header_written: false,
+        }
+    }
+
+    pub fn write(&mut self, series: &TimeSeries) -> io::Result<()> {
+        let out = &mut self.out;
+        if !self.header_written {
+            writeln!(out, "name,seconds,clocks,page_faults")?;
+            self.header_written = true;
+        }
+
+        for point in &series.points {
+            write_csv_str(out, &series.name)?;
+            writeln!(
+                out,
+                ",{},{},{}",
+                series.seconds(point.at),
+                point.clocks,
+                point.page_faults
+            )?;
+        }
+        out.flush()
+    }
+}
+
+impl SeriesWriter<BufWriter<File>> {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(SeriesWriter::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+pub const SERIES_ENV: &str = "REP_TEST_SERIES";
+pub const SERIES_PLOT_ENV: &str = "REP_TEST_SERIES_PLOT";
+
+// `REP_TEST_SERIES=series.csv`
+pub fn series_writer_from_env() -> Result<Option<SeriesWriter<BufWriter<File>>>, ConfigError> {
+    let Some(path) = env::var_os(SERIES_ENV) else {
+        return Ok(None);
+    };
+
+    SeriesWriter::create(Path::new(&path))
+        .map(Some)
+        .map_err(|it| ConfigError::file(SERIES_ENV, it))
+}
+
+pub fn plot_from_env() -> bool {
+    env::var_os(SERIES_PLOT_ENV).is_some()
+}
+
+#[test]
+fn writes_series_csv() {
+    let series = TimeSeries {
+        name: "read, warm".to_string(),
+        timer_frequency: 1000,
+        points: vec![
+            SeriesPoint {
+                at: 0,
+                clocks: 30,
+                page_faults: 2,
+            },
+            SeriesPoint {
+                at: 500,
+                clocks: 20,
+                page_faults: 0,
+            },
+        ],
+    };
+    let mut writer = SeriesWriter::new(Vec::new());
+    writer.write(&series).unwrap();
+
+    assert_eq!(
+        String::from_utf8(writer.out).unwrap(),
+        "name,seconds,clocks,page_faults\n\"read, warm\",0,30,2\n\"read, warm\",0.5,20,0\n"
+    );
+}
+
+#[test]
+fn plots_fastest_run_per_column() {
+    let series = TimeSeries {
+        name: "warm-up".to_string(),
+        timer_frequency: 1000,
+        points: (0..100)
+            .map(|it| SeriesPoint {
+                at: it * 10,
+                clocks: if it < 50 { 200 } else { 100 },
+                page_faults: 0,
+            })
+            .collect(),
+    };
+    let chart = Chart {
+        width: 10,
+        height: 2,
+        unicode: false,
+        log_x: true,
+    };
+    let mut out = Vec::new();
+    series.plot(&mut out, &chart).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[0], "clocks per run by ms since start of warm-up");
+    assert_eq!(lines[1], "       200 |#####");
+    assert_eq!(lines[2], "           |##########");
+}
+
+#[test]
+fn records_every_run() {
+    use super::{RepTester, config::RepConfig};
+
+    let config = RepConfig::default().with_max_runs(10);
+    let mut tester = RepTester::new().unwrap().with_config(config);
+    tester.print = false;
+    tester.record_series = true;
+
+    tester.init("series", 1);
+    while tester.should_continue() {
+        tester.start_run();
+        tester.end_run();
+    }
+    tester.finish().unwrap();
+
+    let series = tester.series().unwrap();
+    assert_eq!(series.points.len(), 10);
+    assert!(series.points.windows(2).all(|it| it[0].at < it[1].at));
+}
@@ -0,0 +1,154 @@
+use std::fmt::Write;
+
+pub struct Histogram {
+    pub min: u64,
+    pub bucket_width: u64,
+    pub counts: Vec<u64>,
+}
+
+impl Histogram {
+    pub fn of(sorted: &[u64], buckets: usize) -> Histogram {
+        assert!(buckets > 0);
+        let (Some(&min), Some(&max)) = (sorted.first(), sorted.last()) else {
+            return Histogram {
+                min: 0,
+                bucket_width: 0,
+                counts: Vec::new(),
+            };
+        };
+
+        let bucket_width = ((max - min) / buckets as u64) + 1;
+        let mut counts = vec![0; buckets];
+        for value in sorted {
+            counts[((value - min) / bucket_width) as usize] += 1;
+        }
+
+        Histogram {
+            min,
+            bucket_width,
+            counts,
+        }
+    }
+
+    pub fn to_string(&self, bar_width: usize) -> String {
+        let peak = self.counts.iter().copied().max().unwrap_or(0).max(1);
+        let mut out = String::new();
+
+        for (idx, count) in self.counts.iter().enumerate() {
+            let from = self.min + idx as u64 * self.bucket_width;
+            let bar = (*count as usize * bar_width).div_ceil(peak as usize);
+            writeln!(
+                out,
+                "{:>14} | {:<bar_width$} {}",
+                from,
+                "#".repeat(bar),
+                count,
+                bar_width = bar_width
+            )
+            .unwrap();
+        }
+
+        out
+    }
+}
+
+pub struct SampleStats {
+    pub median: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub histogram: Histogram,
+}
+
+impl SampleStats {
+    pub const HISTOGRAM_BUCKETS: usize = 10;
+
+    pub fn of(sorted: &[u64]) -> Option<SampleStats> {
+        if sorted.is_empty() {
+            return None;
+        }
+
+        let mean = sorted.iter().map(|it| *it as f64).sum::<f64>() / sorted.len() as f64;
+        let variance = if sorted.len() > 1 {
+            sorted
+                .iter()
+                .map(|it| (*it as f64 - mean) * (*it as f64 - mean))
+                .sum::<f64>()
+                / (sorted.len() - 1) as f64
+        } else {
+            0.0
+        };
+
+        Some(SampleStats {
+            median: percentile(sorted, 0.5),
+            p90: percentile(sorted, 0.9),
+            p99: percentile(sorted, 0.99),
+            mean,
+            std_dev: variance.sqrt(),
+            histogram: Histogram::of(sorted, SampleStats::HISTOGRAM_BUCKETS),
+        })
+    }
+
+    pub fn relative_std_dev(&self) -> f64 {
+        if self.mean == 0.0 {
+            return 0.0;
+        }
+        self.std_dev / self.mean
+    }
+}
+
+// linear interpolation between closest ranks
+pub fn percentile(sorted: &[u64], p: f64) -> f64 {
+    assert!(!sorted.is_empty());
+    assert!((0.0..=1.0).contains(&p));
+
+    let rank = p * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let fraction = rank - lower as f64;
+
+    sorted[lower] as f64 + (sorted[upper] as f64 - sorted[lower] as f64) * fraction
+}
+
+// index of the sample closest to the percentile, for picking a whole run vector
+pub fn percentile_idx(len: usize, p: f64) -> usize {
+    assert!(len > 0);
+    (p * (len - 1) as f64).round() as usize
+}
+
+#[test]
+fn percentiles() {
+    let sorted: Vec<u64> = (1..=100).collect();
+
+    assert_eq!(percentile(&sorted, 0.0), 1.0);
+    assert_eq!(percentile(&sorted, 1.0), 100.0);
+    assert_eq!(percentile(&sorted, 0.5), 50.5);
+    assert!((percentile(&sorted, 0.9) - 90.1).abs() < 1e-9);
+    assert_eq!(percentile(&[7], 0.99), 7.0);
+    assert_eq!(percentile_idx(100, 0.5), 50);
+    assert_eq!(percentile_idx(1, 0.99), 0);
+}
+
+#[test]
+fn sample_stats() {
+    let stats = SampleStats::of(&[2, 4, 4, 4, 5, 5, 7, 9]).unwrap();
+
+    assert_eq!(stats.mean, 5.0);
+    assert!((stats.std_dev - (32.0f64 / 7.0).sqrt()).abs() < 1e-9);
+    assert_eq!(stats.median, 4.5);
+    assert_eq!(stats.histogram.counts.iter().sum::<u64>(), 8);
+    assert_eq!(stats.histogram.min, 2);
+    assert!(SampleStats::of(&[]).is_none());
+}
+
+#[test]
+fn bimodal_histogram() {
+    let mut samples = vec![100; 50];
+    samples.extend(vec![1_000; 50]);
+    let histogram = Histogram::of(&samples, 10);
+
+    assert_eq!(histogram.counts[0], 50);
+    assert_eq!(histogram.counts[9], 50);
+    assert_eq!(histogram.counts[1..9].iter().sum::<u64>(), 0);
+}
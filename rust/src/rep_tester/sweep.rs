@@ -0,0 +1,312 @@
+use std::io::{self, Write};
+
+use super::{MeasurementKind, PerformanceMeasurement, RepTester, error::RepTestError, units::Unit};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SweepRange {
+    // `from..=to` by `step`
+    Linear {
+        from: u64,
+        to: u64,
+        step: u64,
+    },
+    // 1 << from_bits ..= 1 << to_bits
+    PowersOfTwo {
+        from_bits: u32,
+        to_bits: u32,
+    },
+    // every power of two from `from_bits` to `to_bits` split into 1 << step_bits even steps,
+    // 14..=15 with 3 step bits is 16kB, 18kB, 20kB, ..., 30kB, 32kB, ..., 60kB
+    SubSteps {
+        from_bits: u32,
+        to_bits: u32,
+        step_bits: u32,
+    },
+}
+
+impl SweepRange {
+    pub fn points(&self) -> Vec<u64> {
+        match *self {
+            SweepRange::Linear { from, to, step } => {
+                assert!(step > 0, "sweep step must be positive");
+                (from..=to).step_by(step as usize).collect()
+            }
+            SweepRange::PowersOfTwo { from_bits, to_bits } => {
+                (from_bits..=to_bits).map(|bits| 1 << bits).collect()
+            }
+            SweepRange::SubSteps {
+                from_bits,
+                to_bits,
+                step_bits,
+            } => {
+                assert!(
+                    step_bits <= from_bits,
+                    "step_bits must not exceed from_bits"
+                );
+                (from_bits..=to_bits)
+                    .flat_map(|bits| {
+                        (0..1 << step_bits)
+                            .map(move |step| (1 << bits) | (step << (bits - step_bits)))
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+// `cache_1MB`, `cache_1152kB`, `cache_100B`
+pub fn size_name(prefix: &str, bytes: u64) -> String {
+    format!("{}_{}", prefix, size_label(bytes))
+}
+
+// in the largest unit that divides evenly
+pub fn size_label(bytes: u64) -> String {
+    const KB: u64 = 1 << 10;
+    const MB: u64 = 1 << 20;
+    const GB: u64 = 1 << 30;
+
+    if bytes >= GB && bytes.is_multiple_of(GB) {
+        format!("{}GB", bytes / GB)
+    } else if bytes >= MB && bytes.is_multiple_of(MB) {
+        format!("{}MB", bytes / MB)
+    } else if bytes >= KB && bytes.is_multiple_of(KB) {
+        format!("{}kB", bytes / KB)
+    } else {
+        format!("{}B", bytes)
+    }
+}
+
+pub struct SweepRow {
+    pub param: u64,
+    pub name: String,
+    pub bytes: u64,
+    pub best: PerformanceMeasurement,
+    pub avg: PerformanceMeasurement,
+}
+
+pub struct SweepTable {
+    pub param_name: String,
+    pub rows: Vec<SweepRow>,
+}
+
+impl SweepTable {
+    // what the rows count, from the first one
+    pub fn unit(&self) -> Unit {
+        self.rows.first().map_or(Unit::Bytes, |it| it.best.unit)
+    }
+
+    // mb/s for bytes, `unit` per second otherwise
+    fn rate(&self, measurement: &PerformanceMeasurement) -> f64 {
+        match self.unit() {
+            Unit::Bytes => measurement.throughput_mb(),
+            _ => measurement.rate(),
+        }
+    }
+
+    fn rate_name(&self) -> String {
+        match self.unit() {
+            Unit::Bytes => "mb/s".to_string(),
+            unit => format!("{}/s", unit.name()),
+        }
+    }
+
+    // first two columns match the `Size,Throughput` sheets, throughput is in mb/s,
+    // sweeps of other units write `Rate` columns in `unit` per second instead
+    pub fn write_csv(&self, out: &mut impl Write) -> io::Result<()> {
+        match self.unit() {
+            Unit::Bytes => writeln!(out, "{},Throughput,AvgThroughput", self.param_name)?,
+            unit => writeln!(
+                out,
+                "{0},Rate({1}/s),AvgRate({1}/s)",
+                self.param_name,
+                unit.name()
+            )?,
+        }
+        for row in &self.rows {
+            writeln!(
+                out,
+                "{},{},{}",
+                row.param,
+                self.rate(&row.best),
+                self.rate(&row.avg)
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn print(&self, out: &mut impl Write) -> io::Result<()> {
+        let name_width = self
+            .rows
+            .iter()
+            .map(|it| it.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("name".len());
+
+        writeln!(
+            out,
+            "{:>12} | {:<w$} | {:>14} | {:>14}",
+            self.param_name,
+            "name",
+            format!("best {}", self.rate_name()),
+            format!("avg {}", self.rate_name()),
+            w = name_width
+        )?;
+        for row in &self.rows {
+            writeln!(
+                out,
+                "{:>12} | {:<w$} | {:>14.3} | {:>14.3}",
+                row.param,
+                row.name,
+                self.rate(&row.best),
+                self.rate(&row.avg),
+                w = name_width
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl RepTester {
+    // `run` is called once per point and is expected to do a single `rep_run!`,
+    // the first failed point stops the sweep, points which never ran are left out of the table
+    pub fn sweep(
+        &mut self,
+        param_name: &str,
+        range: SweepRange,
+        mut run: impl FnMut(&mut RepTester, u64) -> Result<(), RepTestError>,
+    ) -> Result<SweepTable, RepTestError> {
+        let points = range.points();
+        let mut rows = Vec::with_capacity(points.len());
+
+        for param in points {
+            self.clear();
+            run(self, param)?;
+
+            if let Some(record) = self.record() {
+                rows.push(SweepRow {
+                    param,
+                    name: record.name.to_string(),
+                    bytes: record.bytes,
+                    best: record.best,
+                    avg: self.measurement(MeasurementKind::Avg),
+                });
+            }
+        }
+
+        Ok(SweepTable {
+            param_name: param_name.to_string(),
+            rows,
+        })
+    }
+}
+
+#[test]
+fn sweep_ranges() {
+    assert_eq!(
+        SweepRange::Linear {
+            from: 1,
+            to: 10,
+            step: 4
+        }
+        .points(),
+        [1, 5, 9]
+    );
+    assert_eq!(
+        SweepRange::PowersOfTwo {
+            from_bits: 4,
+            to_bits: 6
+        }
+        .points(),
+        [16, 32, 64]
+    );
+    assert_eq!(
+        SweepRange::SubSteps {
+            from_bits: 4,
+            to_bits: 5,
+            step_bits: 2
+        }
+        .points(),
+        [16, 20, 24, 28, 32, 40, 48, 56]
+    );
+}
+
+#[test]
+fn size_names() {
+    assert_eq!(size_name("cache", 1 << 20), "cache_1MB");
+    assert_eq!(size_name("cache", (1 << 20) + (1 << 17)), "cache_1152kB");
+    assert_eq!(size_name("cache", 18432), "cache_18kB");
+    assert_eq!(size_name("cache", 100), "cache_100B");
+    assert_eq!(size_label(1 << 30), "1GB");
+}
+
+#[test]
+fn sweep_collects_rows() {
+    use super::config::RepConfig;
+    use crate::rep_run;
+
+    let mut tester = RepTester::new()
+        .unwrap()
+        .with_config(RepConfig::default().with_max_runs(5));
+    tester.print = false;
+    let range = SweepRange::PowersOfTwo {
+        from_bits: 8,
+        to_bits: 10,
+    };
+
+    let table = tester
+        .sweep("Size", range, |tester, size| {
+            let mut buf = vec![0u8; size as usize];
+            rep_run!(
+                tester,
+                name = &size_name("fill", size),
+                len = size,
+                block = {
+                    buf.fill(1);
+                }
+            )
+        })
+        .unwrap();
+
+    let params: Vec<u64> = table.rows.iter().map(|it| it.param).collect();
+    assert_eq!(params, [256, 512, 1024]);
+    assert_eq!(table.rows[2].name, "fill_1kB");
+    assert_eq!(table.rows[2].bytes, 1024);
+
+    let mut csv = Vec::new();
+    table.write_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    assert_eq!(csv.lines().count(), 4);
+    assert_eq!(csv.lines().next(), Some("Size,Throughput,AvgThroughput"));
+    assert!(csv.lines().nth(1).unwrap().starts_with("256,"));
+}
+
+#[test]
+fn writes_rate_of_declared_unit() {
+    let measurement = PerformanceMeasurement {
+        bytes: 1000,
+        time: 0.5,
+        clocks: 100.0,
+        unit: Unit::Iterations,
+        ..Default::default()
+    };
+    let table = SweepTable {
+        param_name: "Loops".to_string(),
+        rows: vec![SweepRow {
+            param: 1000,
+            name: "loop".to_string(),
+            bytes: 1000,
+            best: measurement,
+            avg: measurement,
+        }],
+    };
+
+    let mut csv = Vec::new();
+    table.write_csv(&mut csv).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "Loops,Rate(iter/s),AvgRate(iter/s)\n1000,2000,2000\n"
+    );
+}
@@ -0,0 +1,335 @@
+use std::{
+    io::{self, Write, stdout},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        Barrier, Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread,
+};
+
+use crate::{
+    core_affinity,
+    time::timer::{Timer, TimerKind},
+};
+
+use super::{PerformanceMeasurement, RepTester, error::RepTestError};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThreadOptions {
+    pub threads: usize,
+    // pin worker `i` to the i-th core of the process affinity mask, wrapping around
+    pub pin: bool,
+}
+
+impl ThreadOptions {
+    pub fn new(threads: usize) -> ThreadOptions {
+        assert!(threads > 0, "at least one worker thread is required");
+        ThreadOptions { threads, pin: true }
+    }
+
+    pub fn unpinned(mut self) -> Self {
+        self.pin = false;
+        self
+    }
+}
+
+pub struct ThreadStats {
+    pub thread: usize,
+    pub core: Option<usize>,
+    pub runs: u64,
+    pub best: PerformanceMeasurement,
+    pub avg: PerformanceMeasurement,
+}
+
+pub struct ThreadedRun {
+    pub threads: Vec<ThreadStats>,
+    // the whole round, from releasing the workers until the slowest one is done
+    pub aggregate: PerformanceMeasurement,
+}
+
+impl ThreadedRun {
+    // upper bound of the aggregate, threads rarely hit their best in the same round
+    pub fn sum_of_best_mb(&self) -> f64 {
+        self.threads.iter().map(|it| it.best.throughput_mb()).sum()
+    }
+
+    pub fn print(&self, out: &mut impl Write) -> io::Result<()> {
+        for it in &self.threads {
+            let core = it
+                .core
+                .map_or("unpinned".to_string(), |core| format!("cpu {}", core));
+            writeln!(
+                out,
+                "thread {} ({}, {} runs): best {}; avg {}",
+                it.thread,
+                core,
+                it.runs,
+                it.best.to_string(),
+                it.avg.to_string()
+            )?;
+        }
+        writeln!(
+            out,
+            "aggregate: {:.3} mb/s (sum of per-thread best {:.3} mb/s)\n",
+            self.aggregate.throughput_mb(),
+            self.sum_of_best_mb()
+        )
+    }
+}
+
+struct WorkerClocks {
+    core: Option<usize>,
+    runs: u64,
+    best: u64,
+    total: u64,
+}
+
+// pinned, with its own timer and state, or the reason it can't run
+fn prepare_worker<S>(
+    thread: usize,
+    core: Option<usize>,
+    timer_kind: TimerKind,
+    setup: &(impl Fn(usize) -> S + Sync),
+) -> Result<(Box<dyn Timer>, S), RepTestError> {
+    if let Some(core) = core {
+        core_affinity::pin_current_thread(core).map_err(|_| RepTestError::PinFailed { thread })?;
+    }
+    let timer = timer_kind.create().ok_or(RepTestError::NoTimer)?;
+    let state = panic::catch_unwind(AssertUnwindSafe(|| setup(thread)))
+        .map_err(|_| RepTestError::WorkerPanicked { thread })?;
+
+    Ok((timer, state))
+}
+
+impl RepTester {
+    // runs `block` on every worker in lockstep, the tester itself measures whole rounds,
+    // so stop criteria, reporters and baselines see the aggregate throughput.
+    // `setup` runs once on each worker after pinning, so memory is first touched there.
+    // Don't pin the process with `core_affinity::set_single_core` beforehand.
+    pub fn run_threaded<S>(
+        &mut self,
+        name: &str,
+        bytes_per_thread: u64,
+        options: ThreadOptions,
+        setup: impl Fn(usize) -> S + Sync,
+        block: impl Fn(&mut S) + Sync,
+    ) -> Result<ThreadedRun, RepTestError> {
+        let cores = match options.pin {
+            true => core_affinity::available_cores().map_err(|_| RepTestError::NoAffinity)?,
+            false => Vec::new(),
+        };
+        // go/done for every round
+        let barrier = Barrier::new(options.threads + 1);
+        let stop = AtomicBool::new(false);
+        // the first worker whose block panicked, the round it happened in isn't counted
+        let failure: Mutex<Option<RepTestError>> = Mutex::new(None);
+        // every worker reports whether it's ready before the first round
+        let (ready_tx, ready_rx) = mpsc::channel::<Result<(), RepTestError>>();
+        // every worker reads its own timer of the same kind, perf counters are per thread
+        let timer_kind = self.timer_kind();
+
+        self.clear();
+        self.init(name, bytes_per_thread * options.threads as u64);
+
+        let workers: Vec<WorkerClocks> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..options.threads)
+                .map(|thread| {
+                    let core = (!cores.is_empty()).then(|| cores[thread % cores.len()]);
+                    let (barrier, stop, failure) = (&barrier, &stop, &failure);
+                    let (setup, block) = (&setup, &block);
+                    let ready_tx = ready_tx.clone();
+
+                    scope.spawn(move || {
+                        let prepared = prepare_worker(thread, core, timer_kind, setup);
+                        ready_tx
+                            .send(prepared.as_ref().map(|_| ()).map_err(|it| *it))
+                            .expect("runner must wait for workers");
+                        let Ok((timer, mut state)) = prepared else {
+                            // released together with the others once the runner sees the error
+                            barrier.wait();
+                            return None;
+                        };
+                        let mut clocks = WorkerClocks {
+                            core,
+                            runs: 0,
+                            best: u64::MAX,
+                            total: 0,
+                        };
+
+                        loop {
+                            barrier.wait();
+                            if stop.load(Ordering::Acquire) {
+                                break;
+                            }
+                            let start = timer.now();
+                            let result =
+                                panic::catch_unwind(AssertUnwindSafe(|| block(&mut state)));
+                            let end = timer.now();
+                            // only the runner sets `stop`, while every worker waits at a barrier,
+                            // a worker still on its way to read it would skip the done otherwise
+                            if result.is_err() {
+                                failure
+                                    .lock()
+                                    .unwrap()
+                                    .get_or_insert(RepTestError::WorkerPanicked { thread });
+                            }
+                            barrier.wait();
+                            // meets the others at the next go, where the runner has set `stop`
+                            if result.is_err() {
+                                continue;
+                            }
+
+                            let elapsed = end.wrapping_sub(start);
+                            clocks.runs += 1;
+                            clocks.total += elapsed;
+                            clocks.best = clocks.best.min(elapsed);
+                        }
+
+                        Some(clocks)
+                    })
+                })
+                .collect();
+            drop(ready_tx);
+
+            let ready: Vec<Result<(), RepTestError>> = (0..options.threads)
+                .map(|_| ready_rx.recv().expect("worker must report"))
+                .collect();
+            // an errored tester stops before the first round
+            if let Some(error) = ready.into_iter().find_map(Result::err) {
+                self.error(error);
+            }
+
+            while self.should_continue() {
+                self.start_run();
+                barrier.wait();
+                barrier.wait();
+                if let Some(error) = *failure.lock().unwrap() {
+                    self.error(error);
+                    break;
+                }
+                self.end_run();
+
+                if self.print {
+                    self.print();
+                }
+            }
+            stop.store(true, Ordering::Release);
+            barrier.wait();
+
+            handles
+                .into_iter()
+                .filter_map(|it| it.join().expect("worker must not panic"))
+                .collect()
+        });
+        self.finish()?;
+
+        let run = ThreadedRun {
+            threads: workers
+                .iter()
+                .enumerate()
+                .map(|(thread, it)| ThreadStats {
+                    thread,
+                    core: it.core,
+                    runs: it.runs,
+                    best: self.thread_measurement(it.best, bytes_per_thread),
+                    avg: self.thread_measurement(it.total / it.runs.max(1), bytes_per_thread),
+                })
+                .collect(),
+            aggregate: self.measurement(super::MeasurementKind::Best),
+        };
+        if self.print {
+            run.print(&mut stdout()).unwrap();
+        }
+
+        Ok(run)
+    }
+
+    fn thread_measurement(&self, clocks: u64, bytes: u64) -> PerformanceMeasurement {
+        if clocks == 0 || clocks == u64::MAX {
+            return PerformanceMeasurement::nil();
+        }
+
+        PerformanceMeasurement {
+            bytes,
+            time: clocks as f64 / self.timer_frequency as f64,
+            clocks: clocks as f64,
+            ..Default::default()
+        }
+    }
+}
+
+#[test]
+fn runs_workers_in_lockstep() {
+    use super::config::RepConfig;
+    use std::sync::atomic::AtomicU64;
+
+    let config = RepConfig::default().with_max_runs(20);
+    let mut tester = RepTester::new().unwrap().with_config(config);
+    tester.print = false;
+    let rounds = AtomicU64::new(0);
+
+    let run = tester
+        .run_threaded(
+            "lockstep",
+            1024,
+            ThreadOptions::new(3).unpinned(),
+            |thread| vec![thread as u8; 1024],
+            |buf| {
+                buf.iter_mut().for_each(|it| *it = it.wrapping_add(1));
+                rounds.fetch_add(1, Ordering::Relaxed);
+            },
+        )
+        .unwrap();
+
+    assert_eq!(rounds.load(Ordering::Relaxed), 60);
+    assert_eq!(run.threads.len(), 3);
+    assert!(run.threads.iter().all(|it| it.runs == 20));
+    assert!(run.threads.iter().all(|it| it.core.is_none()));
+    assert_eq!(run.aggregate.bytes, 3 * 1024);
+    assert!(run.threads[0].best.clocks <= run.threads[0].avg.clocks);
+}
+
+#[test]
+fn fails_without_hanging_when_a_worker_cannot_start() {
+    let mut tester = RepTester::new().unwrap();
+    tester.print = false;
+
+    let run = tester.run_threaded(
+        "setup panics",
+        1024,
+        ThreadOptions::new(3).unpinned(),
+        |thread| {
+            assert!(thread != 1, "no memory for worker 1");
+            vec![0u8; 1024]
+        },
+        |buf| buf.fill(1),
+    );
+
+    assert_eq!(run.err(), Some(RepTestError::WorkerPanicked { thread: 1 }));
+}
+
+#[test]
+fn fails_without_hanging_when_a_block_panics() {
+    use std::sync::atomic::AtomicU64;
+
+    let mut tester = RepTester::new().unwrap();
+    tester.print = false;
+    let blocks = AtomicU64::new(0);
+
+    let run = tester.run_threaded(
+        "block panics",
+        1024,
+        ThreadOptions::new(3).unpinned(),
+        |thread| thread,
+        |thread| {
+            // a few good rounds first
+            let n = blocks.fetch_add(1, Ordering::Relaxed);
+            assert!(*thread != 2 || n < 9, "worker 2 broke");
+        },
+    );
+
+    assert_eq!(run.err(), Some(RepTestError::WorkerPanicked { thread: 2 }));
+    assert_eq!(tester.run.runs, 3);
+}
@@ -0,0 +1,80 @@
+use std::env;
+
+use super::config::ConfigError;
+
+// what `len` of a rep test counts
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Unit {
+    #[default]
+    Bytes,
+    Iterations,
+    Elements,
+    Instructions,
+}
+
+impl Unit {
+    pub fn name(self) -> &'static str {
+        match self {
+            Unit::Bytes => "B",
+            Unit::Iterations => "iter",
+            Unit::Elements => "elem",
+            Unit::Instructions => "ops",
+        }
+    }
+}
+
+// prefixes of byte amounts, counts of anything else always use SI
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Prefixes {
+    // 1000-based kB, MB, GB
+    Si,
+    // 1024-based KiB, MiB, GiB
+    #[default]
+    Iec,
+}
+
+pub const UNITS_ENV: &str = "REP_TEST_UNITS";
+
+impl Prefixes {
+    // `REP_TEST_UNITS=si|iec`
+    pub fn from_env() -> Result<Prefixes, ConfigError> {
+        match env::var(UNITS_ENV).as_deref() {
+            Ok("si") => Ok(Prefixes::Si),
+            Ok("iec") | Err(_) => Ok(Prefixes::Iec),
+            Ok(other) => Err(ConfigError::invalid(UNITS_ENV, "'si' or 'iec'", other)),
+        }
+    }
+
+    // `12.345 GiB`, `1.200 kiter`
+    pub fn format(self, value: f64, unit: Unit) -> String {
+        let (base, prefixes): (f64, [&str; 5]) = match (self, unit) {
+            (Prefixes::Iec, Unit::Bytes) => (1024.0, ["", "Ki", "Mi", "Gi", "Ti"]),
+            (Prefixes::Si, Unit::Bytes) => (1000.0, ["", "k", "M", "G", "T"]),
+            _ => (1000.0, ["", "k", "M", "G", "T"]),
+        };
+
+        let mut scaled = value;
+        let mut idx = 0;
+        while scaled.abs() >= base && idx + 1 < prefixes.len() {
+            scaled /= base;
+            idx += 1;
+        }
+
+        format!("{:.3} {}{}", scaled, prefixes[idx], unit.name())
+    }
+}
+
+#[test]
+fn formats_prefixes() {
+    assert_eq!(Prefixes::Iec.format(1536.0, Unit::Bytes), "1.500 KiB");
+    assert_eq!(Prefixes::Si.format(1536.0, Unit::Bytes), "1.536 kB");
+    assert_eq!(
+        Prefixes::Iec.format(3.0 * (1u64 << 30) as f64, Unit::Bytes),
+        "3.000 GiB"
+    );
+    assert_eq!(
+        Prefixes::Iec.format(2_500_000.0, Unit::Iterations),
+        "2.500 Miter"
+    );
+    assert_eq!(Prefixes::Si.format(12.0, Unit::Instructions), "12.000 ops");
+}
@@ -0,0 +1,264 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+};
+
+use super::core::update;
+use crate::pretty_print;
+
+const NODES: usize = 4096;
+// past the end of the node table, the scope only counts for its anchor
+pub(super) const UNTRACKED: u32 = u32::MAX;
+// no node has the root as its child or sibling
+const NONE: u32 = 0;
+
+// an anchor reached through one particular chain of parents
+#[derive(Default)]
+struct Node {
+    anchor: AtomicU32,
+    parent: AtomicU32,
+    first_child: AtomicU32,
+    next_sibling: AtomicU32,
+    inclusive: AtomicU64,
+    exclusive: AtomicU64,
+    hits: AtomicU64,
+}
+
+// written by the owning thread only, like the anchors,
+// `len` is stored last so a reader never sees a half written node
+pub(super) struct NodeTable {
+    nodes: Box<[Node]>,
+    len: AtomicU32,
+    truncated: AtomicBool,
+}
+
+impl NodeTable {
+    pub(super) fn new() -> NodeTable {
+        NodeTable {
+            nodes: (0..NODES).map(|_| Node::default()).collect(),
+            len: AtomicU32::new(1),
+            truncated: AtomicBool::new(false),
+        }
+    }
+
+    // node of `anchor` called from `parent`, created on the first call
+    #[inline(always)]
+    pub(super) fn child(&self, parent: u32, anchor: u32) -> u32 {
+        if parent == UNTRACKED {
+            return UNTRACKED;
+        }
+        let nodes = &self.nodes;
+        let parent_node = &nodes[parent as usize];
+
+        let mut child = parent_node.first_child.load(Ordering::Relaxed);
+        while child != NONE {
+            let node = &nodes[child as usize];
+            if node.anchor.load(Ordering::Relaxed) == anchor {
+                return child;
+            }
+            child = node.next_sibling.load(Ordering::Relaxed);
+        }
+
+        let idx = self.len.load(Ordering::Relaxed);
+        if idx as usize == nodes.len() {
+            self.truncated.store(true, Ordering::Relaxed);
+            return UNTRACKED;
+        }
+        let node = &nodes[idx as usize];
+        node.anchor.store(anchor, Ordering::Relaxed);
+        node.parent.store(parent, Ordering::Relaxed);
+        node.next_sibling.store(
+            parent_node.first_child.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+        parent_node.first_child.store(idx, Ordering::Relaxed);
+        self.len.store(idx + 1, Ordering::Release);
+
+        idx
+    }
+
+    #[inline(always)]
+    pub(super) fn close(&self, node: u32, parent: u32, elapsed: u64) {
+        if node == UNTRACKED {
+            return;
+        }
+        let closed = &self.nodes[node as usize];
+
+        update(&closed.hits, |it| it + 1);
+        update(&closed.inclusive, |it| it.wrapping_add(elapsed));
+        update(&closed.exclusive, |it| it.wrapping_add(elapsed));
+        update(&self.nodes[parent as usize].exclusive, |it| {
+            it.wrapping_sub(elapsed)
+        });
+    }
+
+    pub(super) fn snapshot(&self) -> CallTree {
+        let len = self.len.load(Ordering::Acquire) as usize;
+
+        CallTree {
+            nodes: self.nodes[..len]
+                .iter()
+                .map(|it| CallNode {
+                    anchor: it.anchor.load(Ordering::Relaxed),
+                    parent: it.parent.load(Ordering::Relaxed),
+                    inclusive: it.inclusive.load(Ordering::Relaxed),
+                    exclusive: it.exclusive.load(Ordering::Relaxed),
+                    hits: it.hits.load(Ordering::Relaxed),
+                })
+                .collect(),
+            truncated: self.truncated.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CallNode {
+    pub anchor: u32,
+    pub parent: u32,
+    pub inclusive: u64,
+    pub exclusive: u64,
+    pub hits: u64,
+}
+
+// node 0 is the root, parents come before their children
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallTree {
+    pub nodes: Vec<CallNode>,
+    // scopes nested past the node table are left out
+    pub truncated: bool,
+}
+
+impl CallTree {
+    pub fn empty() -> CallTree {
+        CallTree {
+            nodes: vec![CallNode {
+                anchor: 0,
+                parent: 0,
+                inclusive: 0,
+                exclusive: 0,
+                hits: 0,
+            }],
+            truncated: false,
+        }
+    }
+
+    // nodes with the same chain of anchors are summed
+    pub fn merge<'a>(trees: impl IntoIterator<Item = &'a CallTree>) -> CallTree {
+        let mut merged = CallTree::empty();
+        let mut by_path: HashMap<(u32, u32), u32> = HashMap::new();
+
+        for tree in trees {
+            merged.truncated |= tree.truncated;
+            let mut mapped = vec![0u32; tree.nodes.len()];
+
+            for (idx, node) in tree.nodes.iter().enumerate().skip(1) {
+                let parent = mapped[node.parent as usize];
+                let target = *by_path.entry((parent, node.anchor)).or_insert_with(|| {
+                    merged.nodes.push(CallNode {
+                        parent,
+                        inclusive: 0,
+                        exclusive: 0,
+                        hits: 0,
+                        ..*node
+                    });
+                    merged.nodes.len() as u32 - 1
+                });
+                mapped[idx] = target;
+
+                let target = &mut merged.nodes[target as usize];
+                target.inclusive = target.inclusive.wrapping_add(node.inclusive);
+                target.exclusive = target.exclusive.wrapping_add(node.exclusive);
+                target.hits += node.hits;
+            }
+        }
+
+        merged
+    }
+
+    // slowest first
+    pub fn children(&self, node: u32) -> Vec<u32> {
+        let mut children: Vec<u32> = (1..self.nodes.len() as u32)
+            .filter(|it| self.nodes[*it as usize].parent == node)
+            .collect();
+        children.sort_by_key(|it| std::cmp::Reverse(self.nodes[*it as usize].inclusive));
+
+        children
+    }
+
+    // percentages of `total_clocks`, total then self time
+    pub fn print(
+        &self,
+        out: &mut impl Write,
+        labels: &[(u32, &'static str)],
+        total_clocks: u64,
+    ) -> io::Result<()> {
+        let percent = |clocks: u64| clocks as f64 / total_clocks as f64 * 100.0;
+        let mut stack: Vec<(u32, usize)> = self
+            .children(0)
+            .into_iter()
+            .rev()
+            .map(|it| (it, 0))
+            .collect();
+
+        while let Some((idx, depth)) = stack.pop() {
+            let node = &self.nodes[idx as usize];
+            writeln!(
+                out,
+                "{:indent$}- {}[{}]={} ({:.2}%, {:.2}% self)",
+                "",
+                label_name(labels, node.anchor),
+                node.hits,
+                pretty_print(node.inclusive as f64),
+                percent(node.inclusive),
+                percent(node.exclusive),
+                indent = depth * 2
+            )?;
+            stack.extend(
+                self.children(idx)
+                    .into_iter()
+                    .rev()
+                    .map(|it| (it, depth + 1)),
+            );
+        }
+        if self.truncated {
+            writeln!(out, "(call tree truncated at {} nodes)", NODES)?;
+        }
+
+        Ok(())
+    }
+
+    // `outer;inner self_clocks` per node, for flamegraph.pl and speedscope
+    pub fn write_folded(
+        &self,
+        out: &mut impl Write,
+        labels: &[(u32, &'static str)],
+    ) -> io::Result<()> {
+        let mut stack: Vec<(u32, String)> = self
+            .children(0)
+            .into_iter()
+            .rev()
+            .map(|it| (it, label_name(labels, self.nodes[it as usize].anchor)))
+            .collect();
+
+        while let Some((idx, path)) = stack.pop() {
+            let node = &self.nodes[idx as usize];
+            if node.exclusive > 0 && node.exclusive <= node.inclusive {
+                writeln!(out, "{} {}", path, node.exclusive)?;
+            }
+            stack.extend(self.children(idx).into_iter().rev().map(|it| {
+                let name = label_name(labels, self.nodes[it as usize].anchor);
+                (it, format!("{};{}", path, name))
+            }));
+        }
+
+        out.flush()
+    }
+}
+
+pub(super) fn label_name(labels: &[(u32, &'static str)], anchor: u32) -> String {
+    match labels.iter().find(|it| it.0 == anchor) {
+        Some((_, name)) => name.to_string(),
+        None => format!("#{}", anchor),
+    }
+}
@@ -1,72 +1,208 @@
-use std::time::Duration;
-
-use crate::{pretty_print, time::TimeMeasurer};
-
-#[derive(Clone, Copy)]
+use std::{
+    cell::{Cell, RefCell},
+    env,
+    ffi::OsStr,
+    fs::File,
+    io::{self, BufWriter, stdout},
+    marker::PhantomData,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use super::{
+    call_tree::{CallTree, NodeTable},
+    report::{LabelReport, ProfileReport, ThreadReport},
+    trace::{self, EventRing, ThreadTrace, TraceEvent, write_chrome_trace},
+};
+#[cfg(test)]
+use crate::time::frequency::{ClockFrequency, FrequencyMethod};
+use crate::time::timer::{Timer, TimerKind};
+
+const ANCHORS: usize = 4096;
+
+// written by the owning thread only, relaxed loads and stores compile to plain moves
+// and let `finish_end_print_root_profile` read the tables of other threads
+#[derive(Default)]
 struct Anchor {
+    inclusive: AtomicU64,
+    exclusive: AtomicU64,
+    hits: AtomicU64,
+    processed_bytes: AtomicU64,
+    // scopes of this anchor on the thread's stack, only the outermost one of a recursion
+    // adds to inclusive time and bytes, nested ones are already inside it
+    open: AtomicU64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct AnchorTotals {
     inclusive: u64,
     exclusive: u64,
-    occurance: u32,
+    hits: u64,
     processed_bytes: u64,
 }
 
 impl Anchor {
-    fn empty() -> Anchor {
-        Anchor {
-            inclusive: 0,
-            occurance: 0,
-            exclusive: 0,
-            processed_bytes: 0,
+    fn totals(&self) -> AnchorTotals {
+        AnchorTotals {
+            inclusive: self.inclusive.load(Ordering::Relaxed),
+            exclusive: self.exclusive.load(Ordering::Relaxed),
+            hits: self.hits.load(Ordering::Relaxed),
+            processed_bytes: self.processed_bytes.load(Ordering::Relaxed),
         }
     }
+}
 
-    fn occurance(&self) -> u32 {
-        self.occurance
+impl AnchorTotals {
+    fn add(&mut self, other: &AnchorTotals) {
+        self.inclusive = self.inclusive.wrapping_add(other.inclusive);
+        self.exclusive = self.exclusive.wrapping_add(other.exclusive);
+        self.hits += other.hits;
+        self.processed_bytes += other.processed_bytes;
     }
 }
 
-struct Profiler {
-    measurer: TimeMeasurer,
+#[inline(always)]
+pub(super) fn update(value: &AtomicU64, f: impl FnOnce(u64) -> u64) {
+    value.store(f(value.load(Ordering::Relaxed)), Ordering::Relaxed);
+}
+
+struct ThreadTable {
+    name: String,
     anchors: Box<[Anchor]>,
+    tree: NodeTable,
+    events: EventRing,
+}
+
+type NewTimer = Box<dyn Fn() -> Option<Box<dyn Timer>> + Send>;
+
+struct Session {
+    generation: u64,
+    // every thread reads its own timer
+    new_timer: NewTimer,
+    // ring buffer size per thread, 0 without tracing
+    trace_events: usize,
     root_start: u64,
+    // in order of the first mark on each thread
+    tables: Vec<Arc<ThreadTable>>,
 }
 
-impl Profiler {
-    fn new() -> Option<Profiler> {
-        let measurer = TimeMeasurer::init();
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+// generation of the running session, 0 while there is none
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+// a thread's own timer and table, replaced once it marks a scope of a newer session
+struct Local {
+    generation: u64,
+    timer: Box<dyn Timer>,
+    table: Arc<ThreadTable>,
+    scope: Cell<u32>,
+    // call tree node of `scope`
+    node: Cell<u32>,
+}
 
-        measurer.map(|measurer| {
-            let now = measurer.clocks_now();
+thread_local! {
+    static LOCAL: RefCell<Option<Local>> = const { RefCell::new(None) };
+}
 
-            Profiler {
-                anchors: Box::new([Anchor::empty(); 4096]),
-                measurer,
-                root_start: now,
-            }
+impl Local {
+    // None once the session finished, e.g. for a worker which outlives the profile
+    fn register(generation: u64) -> Option<Local> {
+        let mut session = SESSION.lock().unwrap();
+        let session = session.as_mut().filter(|it| it.generation == generation)?;
+
+        let name = match thread::current().name() {
+            Some(name) => name.to_string(),
+            None => format!("thread {}", session.tables.len()),
+        };
+        let table = Arc::new(ThreadTable {
+            name,
+            anchors: (0..ANCHORS).map(|_| Anchor::default()).collect(),
+            tree: NodeTable::new(),
+            events: EventRing::new(session.trace_events),
+        });
+        session.tables.push(table.clone());
+
+        Some(Local {
+            generation,
+            timer: (session.new_timer)().expect("timer must be available"),
+            table,
+            scope: Cell::new(0),
+            node: Cell::new(0),
         })
     }
 }
-static mut PROFILER: Option<Profiler> = None;
-static mut CUR_SCOPE: u32 = 0;
 
+// None while no session is running
+#[inline(always)]
+fn with_local<R>(f: impl FnOnce(&Local) -> R) -> Option<R> {
+    LOCAL.with(|local| {
+        let generation = GENERATION.load(Ordering::Acquire);
+        if generation == 0 {
+            return None;
+        }
+        if local
+            .borrow()
+            .as_ref()
+            .is_none_or(|it| it.generation != generation)
+        {
+            *local.borrow_mut() = Some(Local::register(generation)?);
+        }
+
+        Some(f(local.borrow().as_ref().unwrap()))
+    })
+}
+
+// timer from `TIME_TIMER`
 pub fn start_profile() {
-    unsafe {
-        assert!(matches!(PROFILER, None));
-        let _profiler = Profiler::new();
-        assert!(matches!(_profiler, Some(_)));
-        PROFILER = _profiler;
+    start_profile_with(TimerKind::from_env());
+}
+
+pub fn start_profile_with(kind: TimerKind) {
+    // a profile is still worth having without the trace
+    let trace_events = trace::events_from_env().unwrap_or_else(|error| {
+        eprintln!("warning: {}, tracing is off", error);
+        0
+    });
+    start_profile_with_timers(Box::new(move || kind.create()), trace_events);
+}
+
+fn start_profile_with_timers(new_timer: NewTimer, trace_events: usize) {
+    let generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+    {
+        let mut session = SESSION.lock().unwrap();
+        assert!(session.is_none(), "profiler is already running");
+        *session = Some(Session {
+            generation,
+            new_timer,
+            trace_events,
+            root_start: 0,
+            tables: Vec::new(),
+        });
     }
+    GENERATION.store(generation, Ordering::Release);
+
+    let root_start =
+        with_local(|local| local.timer.now()).expect("invariant, the session was just started");
+    SESSION.lock().unwrap().as_mut().unwrap().root_start = root_start;
 }
 
 pub struct Mark {
     idx: u32,
     start: u64,
-    self_inclusive: u64,
-    after_bytes: u64,
+    bytes: u64,
     parent: u32,
+    node: u32,
+    parent_node: u32,
+    generation: u64,
+    // the scope stack is per thread
+    _thread: PhantomData<*const ()>,
 }
 
-#[allow(static_mut_refs)]
 impl Drop for Mark {
     #[inline(always)]
     fn drop(&mut self) {
@@ -76,123 +212,558 @@ impl Drop for Mark {
 
 impl Mark {
     #[inline(always)]
-    #[allow(static_mut_refs)]
     fn _drop(&mut self) {
-        let prof = unsafe {
-            PROFILER
-                .as_mut()
-                .expect("invariant, anchor must be created with profiler init")
-        };
+        LOCAL.with(|local| {
+            let local = local.borrow();
+            // the session finished while the scope was open
+            let Some(local) = local.as_ref().filter(|it| it.generation == self.generation) else {
+                return;
+            };
+
+            let end = local.timer.now();
+            let elapsed = end.wrapping_sub(self.start);
+            let anchors = &local.table.anchors;
+            let anchor = &anchors[self.idx as usize];
+
+            update(&anchor.hits, |it| it + 1);
+            update(&anchor.open, |it| it - 1);
+            if anchor.open.load(Ordering::Relaxed) == 0 {
+                update(&anchor.inclusive, |it| it.wrapping_add(elapsed));
+                update(&anchor.processed_bytes, |it| it + self.bytes);
+            }
+            // a recursive parent is the same anchor, which nets out to nothing
+            update(&anchor.exclusive, |it| it.wrapping_add(elapsed));
+            update(&anchors[self.parent as usize].exclusive, |it| {
+                it.wrapping_sub(elapsed)
+            });
+            local.table.tree.close(self.node, self.parent_node, elapsed);
+            local.table.events.push(self.idx, self.start, end);
+
+            local.scope.set(self.parent);
+            local.node.set(self.parent_node);
+        });
+    }
 
-        let elapsed = prof.measurer.clocks_now() - self.start;
+    #[inline(always)]
+    fn new(idx: u32, bytes: u64) -> Mark {
+        with_local(|local| {
+            let anchor = &local.table.anchors[idx as usize];
+            let parent = local.scope.replace(idx);
+            update(&anchor.open, |it| it + 1);
+            let parent_node = local.node.get();
+            let node = local.table.tree.child(parent_node, idx);
+            local.node.set(node);
+
+            Mark {
+                idx,
+                bytes,
+                parent,
+                node,
+                parent_node,
+                generation: local.generation,
+                _thread: PhantomData,
+                start: local.timer.now(),
+            }
+        })
+        // outside of a session, generation 0 makes the drop a no-op as well
+        .unwrap_or(Mark {
+            idx: 0,
+            start: 0,
+            bytes: 0,
+            parent: 0,
+            node: 0,
+            parent_node: 0,
+            generation: 0,
+            _thread: PhantomData,
+        })
+    }
+}
 
-        prof.anchors[self.idx as usize].occurance += 1;
-        prof.anchors[self.idx as usize].processed_bytes = self.after_bytes;
+pub fn mark_scope(idx: u32, processed_bytes: u64) -> Mark {
+    Mark::new(idx, processed_bytes)
+}
 
-        prof.anchors[self.idx as usize].inclusive = self.self_inclusive.wrapping_add(elapsed);
-        prof.anchors[self.idx as usize].exclusive = prof.anchors[self.idx as usize]
-            .exclusive
-            .wrapping_add(elapsed);
+struct ThreadTotals {
+    name: String,
+    anchors: Vec<AnchorTotals>,
+    tree: CallTree,
+    events: Vec<TraceEvent>,
+    // overwritten in the ring buffer
+    dropped_events: u64,
+}
 
-        prof.anchors[self.parent as usize].exclusive = prof.anchors[self.parent as usize]
-            .exclusive
-            .wrapping_sub(elapsed);
+struct FinishedProfile {
+    root_start: u64,
+    total_clocks: u64,
+    frequency: u64,
+    frequency_method: &'static str,
+    threads: Vec<ThreadTotals>,
+}
 
-        unsafe { CUR_SCOPE = self.parent };
+impl FinishedProfile {
+    fn aggregate(&self) -> Vec<AnchorTotals> {
+        let mut aggregate = vec![AnchorTotals::default(); ANCHORS];
+        for thread in &self.threads {
+            for (sum, anchor) in aggregate.iter_mut().zip(&thread.anchors) {
+                sum.add(anchor);
+            }
+        }
+
+        aggregate
     }
 
-    #[inline(always)]
-    #[allow(static_mut_refs)]
-    fn new(idx: u32, bytes: u64) -> Mark {
-        let prof = unsafe {
-            PROFILER
-                .as_mut()
-                .expect("invariant, anchor must be created with profiler init")
-        };
-        let scope = unsafe { CUR_SCOPE };
-
-        let mark = Mark {
-            idx,
-            start: prof.measurer.clocks_now(),
-            self_inclusive: prof.anchors[idx as usize].inclusive,
-            parent: scope,
-            after_bytes: prof.anchors[idx as usize].processed_bytes + bytes,
+    fn write_trace(&self, path: &OsStr, labels: &[(u32, &'static str)]) -> io::Result<()> {
+        let threads: Vec<ThreadTrace> = self
+            .threads
+            .iter()
+            .map(|it| ThreadTrace {
+                name: &it.name,
+                events: &it.events,
+            })
+            .collect();
+        let dropped: u64 = self.threads.iter().map(|it| it.dropped_events).sum();
+        if dropped > 0 {
+            eprintln!(
+                "warning: {} oldest trace events were overwritten, raise {}",
+                dropped,
+                trace::TRACE_EVENTS_ENV
+            );
+        }
+
+        let mut out = BufWriter::new(File::create(path)?);
+        write_chrome_trace(&mut out, &threads, labels, self.root_start, self.frequency)
+    }
+
+    fn tree(&self) -> CallTree {
+        CallTree::merge(self.threads.iter().map(|it| &it.tree))
+    }
+
+    fn report(&self, labels: &[(u32, &'static str)]) -> ProfileReport {
+        let label_reports = |anchors: &[AnchorTotals]| -> Vec<LabelReport> {
+            labels
+                .iter()
+                .map(|&(idx, label)| {
+                    let anchor = &anchors[idx as usize];
+                    LabelReport {
+                        label,
+                        anchor: idx,
+                        inclusive: anchor.inclusive,
+                        exclusive: anchor.exclusive,
+                        hits: anchor.hits,
+                        processed_bytes: anchor.processed_bytes,
+                    }
+                })
+                .collect()
         };
 
-        unsafe { CUR_SCOPE = idx };
+        ProfileReport {
+            total_clocks: self.total_clocks,
+            frequency: self.frequency,
+            frequency_method: self.frequency_method,
+            labels: label_reports(&self.aggregate()),
+            threads: self
+                .threads
+                .iter()
+                .map(|it| ThreadReport {
+                    name: it.name.clone(),
+                    labels: label_reports(&it.anchors),
+                })
+                .collect(),
+            tree: self.tree(),
+        }
+    }
+}
 
-        mark
+// on the thread which started the profile, after joining the profiled threads
+fn finish_profile() -> Result<FinishedProfile, String> {
+    if GENERATION.load(Ordering::Acquire) == 0 {
+        return Err("profiler was not started".to_string());
     }
+    let (now, frequency) = with_local(|local| {
+        (
+            local.timer.now(),
+            local.timer.frequency(Duration::from_millis(100)),
+        )
+    })
+    .ok_or("profiler was not started")?;
+
+    GENERATION.store(0, Ordering::Release);
+    let session = SESSION
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or("profiler was not started")?;
+    LOCAL.with(|local| *local.borrow_mut() = None);
+
+    Ok(FinishedProfile {
+        root_start: session.root_start,
+        total_clocks: now.wrapping_sub(session.root_start),
+        frequency: frequency.hz,
+        frequency_method: frequency.method.name(),
+        threads: session
+            .tables
+            .iter()
+            .map(|table| {
+                let (events, dropped_events) = table.events.snapshot();
+                ThreadTotals {
+                    name: table.name.clone(),
+                    anchors: table.anchors.iter().map(Anchor::totals).collect(),
+                    tree: table.tree.snapshot(),
+                    events,
+                    dropped_events,
+                }
+            })
+            .collect(),
+    })
 }
 
-#[allow(static_mut_refs)]
-pub fn mark_scope(idx: u32, processed_bytes: u64) -> Mark {
-    Mark::new(idx, processed_bytes)
+// `PROFILE_FOLDED=profile.folded` writes the call tree as folded stacks for flame graphs
+pub const FOLDED_ENV: &str = "PROFILE_FOLDED";
+
+// on the thread which started the profile, after joining the profiled threads,
+// writes the folded stacks and the trace when asked for but prints nothing
+pub fn finish_root_profile(labels: &[(u32, &'static str)]) -> Result<ProfileReport, String> {
+    let profile = finish_profile()?;
+    let report = profile.report(labels);
+
+    if let Some(path) = env::var_os(FOLDED_ENV) {
+        let mut out = BufWriter::new(File::create(path).map_err(|it| it.to_string())?);
+        report
+            .tree
+            .write_folded(&mut out, labels)
+            .map_err(|it| it.to_string())?;
+    }
+    if let Some(path) = env::var_os(trace::TRACE_ENV) {
+        profile
+            .write_trace(&path, labels)
+            .map_err(|it| it.to_string())?;
+    }
+
+    Ok(report)
 }
 
-#[allow(static_mut_refs)]
-pub fn finish_end_print_root_profile(labels: &[(u32, &'static str)]) -> Result<(), String> {
-    unsafe {
-        let profiler = PROFILER.as_mut().unwrap();
+pub fn finish_end_print_root_profile(
+    labels: &[(u32, &'static str)],
+) -> Result<ProfileReport, String> {
+    let report = finish_root_profile(labels)?;
+    report
+        .write_text(&mut stdout())
+        .map_err(|it| it.to_string())?;
 
-        let now = profiler.measurer.clocks_now();
-        let clock_frequency = profiler
-            .measurer
-            .detect_clock_frequency(Duration::from_millis(100));
+    Ok(report)
+}
 
-        let total_execution_time_clocks = (now - profiler.root_start);
-        let total_time_s = (total_execution_time_clocks as f64) / (clock_frequency as f64);
-        let total_time = total_time_s * 1_000.0;
+// one profile per process at a time
+#[cfg(test)]
+static TEST_LOCK: Mutex<()> = Mutex::new(());
 
-        let mut labels_times: Vec<String> = Vec::with_capacity(labels.len());
+#[cfg(test)]
+static FAKE_NOW: AtomicU64 = AtomicU64::new(0);
 
-        for (idx, label_str) in labels {
-            let label = &profiler.anchors[*idx as usize];
-            if label.exclusive == 0 && label.inclusive == 0 {
-                continue;
-            }
-            let percentage =
-                (((label.exclusive) as f64) / (total_execution_time_clocks as f64)) * 100.0;
-
-            let empty = String::new();
-            let throughput = match label.processed_bytes {
-                0 => &empty,
-                bytes => {
-                    let mbytes = (bytes as f64) / (1024.0 * 1024.0);
-                    let gbytes = (bytes as f64) / (1024.0 * 1024.0 * 1024.0);
-                    let execution_time_s = label.inclusive as f64 / clock_frequency as f64;
-                    let throughput = mbytes / execution_time_s;
-                    &format!(" {:.3} GB => {:.2} mb/s", gbytes, throughput)
-                }
-            };
-            let children = if label.inclusive != label.exclusive {
-                let percentage_nested =
-                    (((label.inclusive) as f64) / (total_execution_time_clocks as f64)) * 100.0;
+// moves only when a test calls `tick`
+#[cfg(test)]
+struct FakeTimer;
 
-                &format!(", {:.2}% w/children", percentage_nested)
-            } else {
-                &empty
-            };
+#[cfg(test)]
+impl Timer for FakeTimer {
+    fn kind(&self) -> TimerKind {
+        TimerKind::Default
+    }
+
+    fn now(&self) -> u64 {
+        FAKE_NOW.load(Ordering::Relaxed)
+    }
 
-            labels_times.push(format!(
-                "- {}[{}]={} ({:.2}%{}){}",
-                label_str,
-                label.occurance(),
-                pretty_print(label.inclusive as f64),
-                percentage,
-                children,
-                throughput
-            ));
+    fn frequency(&self, _max_estimation_time: Duration) -> ClockFrequency {
+        ClockFrequency {
+            hz: 1000,
+            method: FrequencyMethod::Calibrated,
         }
+    }
+}
+
+#[cfg(test)]
+fn tick(clocks: u64) {
+    FAKE_NOW.fetch_add(clocks, Ordering::Relaxed);
+}
 
-        println!(
-            "Execution time: {:.2}ms; CPU Frequency ~{}Hz\n{}",
-            total_time,
-            pretty_print(clock_frequency as f64),
-            labels_times.join("\n")
-        );
-        PROFILER = None;
+#[cfg(test)]
+fn fake_profile(body: impl FnOnce()) -> FinishedProfile {
+    let _lock = TEST_LOCK.lock().unwrap();
+    FAKE_NOW.store(0, Ordering::Relaxed);
+    start_profile_with_timers(Box::new(|| Some(Box::new(FakeTimer) as Box<dyn Timer>)), 16);
+
+    body();
+
+    finish_profile().unwrap()
+}
 
-        Ok(())
+#[cfg(test)]
+fn totals(inclusive: u64, exclusive: u64, hits: u64, processed_bytes: u64) -> AnchorTotals {
+    AnchorTotals {
+        inclusive,
+        exclusive,
+        hits,
+        processed_bytes,
     }
 }
+
+#[test]
+fn nested_scopes() {
+    const OUTER: usize = 1;
+    const INNER: usize = 2;
+
+    let profile = fake_profile(|| {
+        let outer = mark_scope(OUTER as u32, 64);
+        tick(10);
+        for _ in 0..2 {
+            let _inner = mark_scope(INNER as u32, 16);
+            tick(5);
+        }
+        tick(1);
+        drop(outer);
+        tick(4);
+    });
+
+    assert_eq!(profile.total_clocks, 25);
+    let anchors = &profile.threads[0].anchors;
+    assert_eq!(anchors[OUTER], totals(21, 11, 1, 64));
+    assert_eq!(anchors[INNER], totals(10, 10, 2, 32));
+}
+
+#[test]
+fn recursion_counts_outermost_scope() {
+    const RECURSIVE: usize = 1;
+
+    fn recurse(depth: u32) {
+        let _mark = mark_scope(RECURSIVE as u32, 10);
+        tick(1);
+        if depth > 0 {
+            recurse(depth - 1);
+        }
+        tick(1);
+    }
+
+    let profile = fake_profile(|| {
+        recurse(2);
+        tick(3);
+        recurse(0);
+    });
+
+    assert_eq!(profile.total_clocks, 11);
+    assert_eq!(profile.threads[0].anchors[RECURSIVE], totals(8, 8, 4, 20));
+}
+
+#[test]
+fn mutual_recursion() {
+    // like parse_unknown -> parse_object -> parse_unknown in the json parser
+    const UNKNOWN: usize = 1;
+    const OBJECT: usize = 2;
+
+    fn unknown(depth: u32) {
+        let _mark = mark_scope(UNKNOWN as u32, 0);
+        tick(1);
+        object(depth);
+    }
+    fn object(depth: u32) {
+        let _mark = mark_scope(OBJECT as u32, 0);
+        tick(2);
+        if depth > 0 {
+            unknown(depth - 1);
+        }
+    }
+
+    let profile = fake_profile(|| unknown(2));
+
+    let anchors = &profile.threads[0].anchors;
+    assert_eq!(anchors[UNKNOWN], totals(9, 3, 3, 0));
+    assert_eq!(anchors[OBJECT], totals(8, 6, 3, 0));
+    assert_eq!(
+        anchors[UNKNOWN].exclusive + anchors[OBJECT].exclusive,
+        profile.total_clocks
+    );
+}
+
+#[test]
+fn merges_thread_tables() {
+    let _lock = TEST_LOCK.lock().unwrap();
+    start_profile_with(TimerKind::Default);
+
+    drop(mark_scope(1, 10));
+    thread::scope(|scope| {
+        for _ in 0..3 {
+            scope.spawn(|| {
+                let outer = mark_scope(1, 100);
+                drop(mark_scope(2, 0));
+                drop(outer);
+            });
+        }
+    });
+
+    let profile = finish_profile().unwrap();
+    assert_eq!(profile.threads.len(), 4);
+    for thread in &profile.threads[1..] {
+        assert_eq!(thread.anchors[1].hits, 1);
+        assert_eq!(thread.anchors[1].processed_bytes, 100);
+        assert_eq!(thread.anchors[2].hits, 1);
+    }
+
+    let aggregate = profile.aggregate();
+    assert_eq!(aggregate[1].hits, 4);
+    assert_eq!(aggregate[1].processed_bytes, 310);
+    assert_eq!(aggregate[2].hits, 3);
+    assert!(aggregate[1].inclusive >= aggregate[2].inclusive);
+    assert!(finish_profile().is_err());
+}
+
+#[test]
+fn builds_call_tree() {
+    const PARSE: u32 = 1;
+    const OBJECT: u32 = 2;
+    const READ: u32 = 3;
+    const LABELS: &[(u32, &str)] = &[(PARSE, "Parse"), (OBJECT, "Object"), (READ, "Read")];
+
+    let profile = fake_profile(|| {
+        drop(mark_scope(READ, 0));
+        let parse = mark_scope(PARSE, 0);
+        tick(2);
+        for _ in 0..2 {
+            let _object = mark_scope(OBJECT, 0);
+            tick(3);
+            let _read = mark_scope(READ, 0);
+            tick(1);
+        }
+        drop(parse);
+    });
+    let tree = profile.tree();
+
+    let parse = tree.children(0)[0];
+    assert_eq!(tree.nodes[parse as usize].inclusive, 10);
+    assert_eq!(tree.nodes[parse as usize].exclusive, 2);
+    let object = tree.children(parse)[0];
+    assert_eq!(tree.nodes[object as usize].hits, 2);
+    assert_eq!(tree.nodes[object as usize].exclusive, 6);
+
+    let mut out = Vec::new();
+    tree.print(&mut out, LABELS, profile.total_clocks).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        concat!(
+            "- Parse[1]=10 (100.00%, 20.00% self)\n",
+            "  - Object[2]=8 (80.00%, 60.00% self)\n",
+            "    - Read[2]=2 (20.00%, 20.00% self)\n",
+            "- Read[1]=0 (0.00%, 0.00% self)\n",
+        )
+    );
+
+    let mut out = Vec::new();
+    tree.write_folded(&mut out, LABELS).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "Parse 2\nParse;Object 6\nParse;Object;Read 2\n"
+    );
+}
+
+#[test]
+fn merges_call_trees_of_threads() {
+    let profile = fake_profile(|| {
+        let _outer = mark_scope(1, 0);
+        thread::scope(|scope| {
+            for _ in 0..2 {
+                scope.spawn(|| {
+                    let _outer = mark_scope(1, 0);
+                    drop(mark_scope(2, 0));
+                });
+            }
+        });
+    });
+    let tree = profile.tree();
+
+    // main: 1, workers: 1 -> 2
+    assert_eq!(tree.nodes.len(), 3);
+    let outer = tree.children(0)[0];
+    assert_eq!(tree.nodes[outer as usize].hits, 3);
+    assert_eq!(tree.nodes[tree.children(outer)[0] as usize].hits, 2);
+}
+
+#[test]
+fn records_trace_events() {
+    let profile = fake_profile(|| {
+        let outer = mark_scope(1, 0);
+        tick(2);
+        drop(mark_scope(2, 0));
+        tick(1);
+        drop(outer);
+    });
+
+    let thread = &profile.threads[0];
+    assert_eq!(thread.dropped_events, 0);
+    assert_eq!(
+        thread.events,
+        [
+            TraceEvent {
+                anchor: 2,
+                start: 2,
+                end: 2
+            },
+            TraceEvent {
+                anchor: 1,
+                start: 0,
+                end: 3
+            },
+        ]
+    );
+}
+
+#[test]
+fn reports_label_totals() {
+    let profile = fake_profile(|| {
+        let outer = mark_scope(1, 0);
+        tick(3);
+        let inner = mark_scope(2, 64);
+        tick(2);
+        drop(inner);
+        drop(outer);
+    });
+
+    let report = profile.report(&[(1, "Outer"), (2, "Inner"), (3, "Unused")]);
+    assert_eq!(report.total_clocks, 5);
+    assert_eq!(report.frequency, 1000);
+    assert_eq!(report.labels.len(), 3);
+    assert_eq!(report.threads.len(), 1);
+
+    let inner = report.label("Inner").unwrap();
+    assert_eq!(
+        (
+            inner.inclusive,
+            inner.exclusive,
+            inner.hits,
+            inner.processed_bytes
+        ),
+        (2, 2, 1, 64)
+    );
+    let outer = report.label("Outer").unwrap();
+    assert_eq!((outer.inclusive, outer.exclusive), (5, 3));
+    assert_eq!(report.label("Unused").unwrap().hits, 0);
+    assert_eq!(report.threads[0].labels, report.labels);
+}
+
+#[test]
+fn ignores_scopes_outside_of_a_session() {
+    let _lock = TEST_LOCK.lock().unwrap();
+    drop(mark_scope(1, 0));
+
+    // a worker which outlives the profile
+    start_profile_with_timers(Box::new(|| Some(Box::new(FakeTimer) as Box<dyn Timer>)), 0);
+    let (finished, wait) = std::sync::mpsc::channel();
+    let worker = thread::spawn(move || {
+        wait.recv().unwrap();
+        drop(mark_scope(2, 0));
+    });
+    let profile = finish_profile().unwrap();
+    finished.send(()).unwrap();
+    worker.join().unwrap();
+
+    assert_eq!(profile.threads.len(), 1);
+    assert_eq!(profile.threads[0].anchors[1].hits, 0);
+}
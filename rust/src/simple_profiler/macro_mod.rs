@@ -92,12 +92,15 @@ macro_rules! profiling_labels {
 
 #[macro_export]
 macro_rules! with_profiling {
-    ($labels:ident => $($t: tt)+) => {
+    ($labels:ident as $report:ident => $($t: tt)+) => {
         $crate::simple_profiler::core::start_profile();
 
         $($t)+
 
-        $crate::simple_profiler::core::finish_end_print_root_profile($labels::ALL).unwrap();
+        let $report = $crate::simple_profiler::core::finish_end_print_root_profile($labels::ALL).unwrap();
+    };
+    ($labels:ident => $($t: tt)+) => {
+        $crate::with_profiling!($labels as __report => $($t)+);
     };
 }
 
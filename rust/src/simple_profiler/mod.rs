@@ -1,3 +1,6 @@
 #[macro_use]
 pub mod core;
+pub mod call_tree;
 pub mod macro_mod;
+pub mod report;
+pub mod trace;
@@ -0,0 +1,269 @@
+use std::io::{self, Write};
+
+use super::call_tree::CallTree;
+use crate::{
+    pretty_print,
+    rep_tester::report::{write_csv_str, write_json_str},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LabelReport {
+    pub label: &'static str,
+    pub anchor: u32,
+    // clocks with nested labels, a recursion counts once
+    pub inclusive: u64,
+    // clocks without nested labels
+    pub exclusive: u64,
+    pub hits: u64,
+    pub processed_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThreadReport {
+    pub name: String,
+    pub labels: Vec<LabelReport>,
+}
+
+// what `finish_end_print_root_profile` prints, every label in the order it was given
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileReport {
+    // wall time since `start_profile`
+    pub total_clocks: u64,
+    pub frequency: u64,
+    pub frequency_method: &'static str,
+    // summed over threads
+    pub labels: Vec<LabelReport>,
+    pub threads: Vec<ThreadReport>,
+    pub tree: CallTree,
+}
+
+impl ProfileReport {
+    pub fn label(&self, name: &str) -> Option<&LabelReport> {
+        self.labels.iter().find(|it| it.label == name)
+    }
+
+    pub fn seconds(&self, clocks: u64) -> f64 {
+        clocks as f64 / self.frequency as f64
+    }
+
+    // percentages are of the wall time, the aggregate of
+    // several threads can add up to more than 100%
+    pub fn write_text(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "Execution time: {:.2}ms; CPU Frequency ~{}Hz ({})",
+            self.seconds(self.total_clocks) * 1_000.0,
+            pretty_print(self.frequency as f64),
+            self.frequency_method
+        )?;
+        self.write_label_lines(out, &self.labels)?;
+        if self.threads.len() > 1 {
+            for thread in &self.threads {
+                writeln!(out, "Thread {}:", thread.name)?;
+                self.write_label_lines(out, &thread.labels)?;
+            }
+        }
+
+        writeln!(out, "Call tree:")?;
+        self.tree
+            .print(out, &self.label_names(), self.total_clocks)?;
+
+        out.flush()
+    }
+
+    fn write_label_lines(&self, out: &mut impl Write, labels: &[LabelReport]) -> io::Result<()> {
+        for label in labels {
+            if label.exclusive == 0 && label.inclusive == 0 {
+                continue;
+            }
+            let percentage = (label.exclusive as f64 / self.total_clocks as f64) * 100.0;
+
+            let children = if label.inclusive != label.exclusive {
+                let percentage_nested = (label.inclusive as f64 / self.total_clocks as f64) * 100.0;
+                format!(", {:.2}% w/children", percentage_nested)
+            } else {
+                String::new()
+            };
+            let throughput = match label.processed_bytes {
+                0 => String::new(),
+                bytes => {
+                    let mbytes = (bytes as f64) / (1024.0 * 1024.0);
+                    let gbytes = (bytes as f64) / (1024.0 * 1024.0 * 1024.0);
+                    let throughput = mbytes / self.seconds(label.inclusive);
+                    format!(" {:.3} GB => {:.2} mb/s", gbytes, throughput)
+                }
+            };
+
+            writeln!(
+                out,
+                "- {}[{}]={} ({:.2}%{}){}",
+                label.label,
+                label.hits,
+                pretty_print(label.inclusive as f64),
+                percentage,
+                children,
+                throughput
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // one object on a single line, labels of the aggregate and of every thread
+    pub fn write_json(&self, out: &mut impl Write) -> io::Result<()> {
+        write!(
+            out,
+            "{{\"total_clocks\":{},\"frequency\":{},\"frequency_method\":",
+            self.total_clocks, self.frequency
+        )?;
+        write_json_str(out, self.frequency_method)?;
+        write!(out, ",\"labels\":")?;
+        write_json_labels(out, &self.labels)?;
+
+        write!(out, ",\"threads\":[")?;
+        for (idx, thread) in self.threads.iter().enumerate() {
+            if idx > 0 {
+                write!(out, ",")?;
+            }
+            write!(out, "{{\"name\":")?;
+            write_json_str(out, &thread.name)?;
+            write!(out, ",\"labels\":")?;
+            write_json_labels(out, &thread.labels)?;
+            write!(out, "}}")?;
+        }
+        writeln!(out, "]}}")?;
+
+        out.flush()
+    }
+
+    // a row per label and thread, the aggregate has an empty thread
+    pub fn write_csv(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "thread,label,hits,inclusive_clocks,exclusive_clocks,processed_bytes,total_clocks,frequency"
+        )?;
+        let aggregate = std::iter::once(("", &self.labels));
+        let threads = self.threads.iter().map(|it| (it.name.as_str(), &it.labels));
+
+        for (thread, labels) in aggregate.chain(threads) {
+            for label in labels {
+                write_csv_str(out, thread)?;
+                write!(out, ",")?;
+                write_csv_str(out, label.label)?;
+                writeln!(
+                    out,
+                    ",{},{},{},{},{},{}",
+                    label.hits,
+                    label.inclusive,
+                    label.exclusive,
+                    label.processed_bytes,
+                    self.total_clocks,
+                    self.frequency
+                )?;
+            }
+        }
+
+        out.flush()
+    }
+
+    fn label_names(&self) -> Vec<(u32, &'static str)> {
+        self.labels.iter().map(|it| (it.anchor, it.label)).collect()
+    }
+}
+
+fn write_json_labels(out: &mut impl Write, labels: &[LabelReport]) -> io::Result<()> {
+    write!(out, "[")?;
+    for (idx, label) in labels.iter().enumerate() {
+        if idx > 0 {
+            write!(out, ",")?;
+        }
+        write!(out, "{{\"label\":")?;
+        write_json_str(out, label.label)?;
+        write!(
+            out,
+            ",\"hits\":{},\"inclusive\":{},\"exclusive\":{},\"processed_bytes\":{}}}",
+            label.hits, label.inclusive, label.exclusive, label.processed_bytes
+        )?;
+    }
+    write!(out, "]")
+}
+
+#[cfg(test)]
+fn test_report() -> ProfileReport {
+    let outer = LabelReport {
+        label: "Outer",
+        anchor: 1,
+        inclusive: 600,
+        exclusive: 400,
+        hits: 1,
+        processed_bytes: 0,
+    };
+    let inner = LabelReport {
+        label: "Inner",
+        anchor: 2,
+        inclusive: 200,
+        exclusive: 200,
+        hits: 2,
+        processed_bytes: 1024 * 1024,
+    };
+    ProfileReport {
+        total_clocks: 1000,
+        frequency: 1000,
+        frequency_method: "spin calibration",
+        labels: vec![outer, inner],
+        threads: vec![ThreadReport {
+            name: "main".to_string(),
+            labels: vec![outer, inner],
+        }],
+        tree: CallTree::empty(),
+    }
+}
+
+#[test]
+fn writes_text_report() {
+    let mut out = Vec::new();
+    test_report().write_text(&mut out).unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        concat!(
+            "Execution time: 1000.00ms; CPU Frequency ~1_000Hz (spin calibration)\n",
+            "- Outer[1]=600 (40.00%, 60.00% w/children)\n",
+            "- Inner[2]=200 (20.00%) 0.001 GB => 5.00 mb/s\n",
+            "Call tree:\n"
+        )
+    );
+}
+
+#[test]
+fn writes_json_report() {
+    let mut out = Vec::new();
+    test_report().write_json(&mut out).unwrap();
+
+    let labels = concat!(
+        "[{\"label\":\"Outer\",\"hits\":1,\"inclusive\":600,\"exclusive\":400,\"processed_bytes\":0}",
+        ",{\"label\":\"Inner\",\"hits\":2,\"inclusive\":200,\"exclusive\":200,\"processed_bytes\":1048576}]"
+    );
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        format!(
+            concat!(
+                "{{\"total_clocks\":1000,\"frequency\":1000,\"frequency_method\":\"spin calibration\"",
+                ",\"labels\":{labels},\"threads\":[{{\"name\":\"main\",\"labels\":{labels}}}]}}\n"
+            ),
+            labels = labels
+        )
+    );
+}
+
+#[test]
+fn writes_csv_report() {
+    let mut out = Vec::new();
+    test_report().write_csv(&mut out).unwrap();
+
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[1], ",Outer,1,600,400,0,1000,1000");
+    assert_eq!(lines[4], "main,Inner,2,200,200,1048576,1000,1000");
+}
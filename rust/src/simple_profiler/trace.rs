@@ -0,0 +1,207 @@
+use std::{
+    env,
+    io::{self, Write},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use super::call_tree::label_name;
+use crate::rep_tester::{
+    config::{ConfigError, env_value},
+    report::write_json_str,
+};
+
+// `PROFILE_TRACE=trace.json` records every scope and writes them for chrome://tracing or Perfetto
+pub const TRACE_ENV: &str = "PROFILE_TRACE";
+// ring buffer size per thread, the oldest scopes are overwritten past it
+pub const TRACE_EVENTS_ENV: &str = "PROFILE_TRACE_EVENTS";
+const DEFAULT_EVENTS: usize = 1 << 16;
+
+// events per thread, 0 when tracing is off
+pub(super) fn events_from_env() -> Result<usize, ConfigError> {
+    if env::var_os(TRACE_ENV).is_none() {
+        return Ok(0);
+    }
+
+    Ok(env_value(TRACE_EVENTS_ENV, "an integer")?.unwrap_or(DEFAULT_EVENTS))
+}
+
+#[derive(Default)]
+struct Slot {
+    anchor: AtomicU64,
+    start: AtomicU64,
+    end: AtomicU64,
+}
+
+// written by the owning thread only, `written` is stored last like the call tree `len`
+pub(super) struct EventRing {
+    slots: Box<[Slot]>,
+    written: AtomicU64,
+}
+
+impl EventRing {
+    // allocated up front, recording never allocates
+    pub(super) fn new(capacity: usize) -> EventRing {
+        EventRing {
+            slots: (0..capacity).map(|_| Slot::default()).collect(),
+            written: AtomicU64::new(0),
+        }
+    }
+
+    #[inline(always)]
+    pub(super) fn push(&self, anchor: u32, start: u64, end: u64) {
+        if self.slots.is_empty() {
+            return;
+        }
+        let written = self.written.load(Ordering::Relaxed);
+        let slot = &self.slots[(written % self.slots.len() as u64) as usize];
+
+        slot.anchor.store(anchor as u64, Ordering::Relaxed);
+        slot.start.store(start, Ordering::Relaxed);
+        slot.end.store(end, Ordering::Relaxed);
+        self.written.store(written + 1, Ordering::Release);
+    }
+
+    // oldest first, with the number of overwritten events
+    pub(super) fn snapshot(&self) -> (Vec<TraceEvent>, u64) {
+        let written = self.written.load(Ordering::Acquire);
+        let capacity = self.slots.len() as u64;
+        let first = written.saturating_sub(capacity);
+
+        let events = (first..written)
+            .map(|idx| {
+                let slot = &self.slots[(idx % capacity) as usize];
+                TraceEvent {
+                    anchor: slot.anchor.load(Ordering::Relaxed) as u32,
+                    start: slot.start.load(Ordering::Relaxed),
+                    end: slot.end.load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+
+        (events, first)
+    }
+}
+
+// a closed scope, in clocks of the thread's timer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceEvent {
+    pub anchor: u32,
+    pub start: u64,
+    pub end: u64,
+}
+
+pub struct ThreadTrace<'a> {
+    pub name: &'a str,
+    pub events: &'a [TraceEvent],
+}
+
+// Trace Event Format, complete ("X") events with microseconds since `origin`
+pub fn write_chrome_trace(
+    out: &mut impl Write,
+    threads: &[ThreadTrace],
+    labels: &[(u32, &'static str)],
+    origin: u64,
+    frequency: u64,
+) -> io::Result<()> {
+    let micros = |clocks: u64| clocks as f64 * 1_000_000.0 / frequency as f64;
+    let pid = std::process::id();
+
+    write!(out, "{{\"traceEvents\":[")?;
+    let mut first = true;
+
+    for (tid, thread) in threads.iter().enumerate() {
+        separate(out, &mut first)?;
+        write!(
+            out,
+            "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":{},\"tid\":{},\"args\":{{\"name\":",
+            pid, tid
+        )?;
+        write_json_str(out, thread.name)?;
+        write!(out, "}}}}")?;
+
+        for event in thread.events {
+            separate(out, &mut first)?;
+            write!(out, "{{\"name\":")?;
+            write_json_str(out, &label_name(labels, event.anchor))?;
+            write!(
+                out,
+                ",\"ph\":\"X\",\"pid\":{},\"tid\":{},\"ts\":{:.3},\"dur\":{:.3}}}",
+                pid,
+                tid,
+                micros(event.start.wrapping_sub(origin)),
+                micros(event.end.wrapping_sub(event.start))
+            )?;
+        }
+    }
+    writeln!(out, "\n]}}")?;
+
+    out.flush()
+}
+
+fn separate(out: &mut impl Write, first: &mut bool) -> io::Result<()> {
+    let separator = if *first { "\n" } else { ",\n" };
+    *first = false;
+    write!(out, "{}", separator)
+}
+
+#[test]
+fn ring_keeps_latest_events() {
+    let ring = EventRing::new(3);
+    for it in 0..5 {
+        ring.push(it, it as u64 * 10, it as u64 * 10 + 5);
+    }
+
+    let (events, dropped) = ring.snapshot();
+    assert_eq!(dropped, 2);
+    let anchors: Vec<u32> = events.iter().map(|it| it.anchor).collect();
+    assert_eq!(anchors, [2, 3, 4]);
+    assert_eq!(events[0].start, 20);
+
+    let disabled = EventRing::new(0);
+    disabled.push(1, 0, 1);
+    assert_eq!(disabled.snapshot(), (Vec::new(), 0));
+}
+
+#[test]
+fn writes_trace_events() {
+    let events = [
+        TraceEvent {
+            anchor: 2,
+            start: 1_500,
+            end: 2_000,
+        },
+        TraceEvent {
+            anchor: 1,
+            start: 1_000,
+            end: 3_000,
+        },
+    ];
+    let threads = [ThreadTrace {
+        name: "main",
+        events: &events,
+    }];
+    let mut out = Vec::new();
+    write_chrome_trace(
+        &mut out,
+        &threads,
+        &[(1, "Outer"), (2, "Inner")],
+        1_000,
+        1_000_000,
+    )
+    .unwrap();
+
+    let pid = std::process::id();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        format!(
+            concat!(
+                "{{\"traceEvents\":[\n",
+                "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":{pid},\"tid\":0,\"args\":{{\"name\":\"main\"}}}},\n",
+                "{{\"name\":\"Inner\",\"ph\":\"X\",\"pid\":{pid},\"tid\":0,\"ts\":500.000,\"dur\":500.000}},\n",
+                "{{\"name\":\"Outer\",\"ph\":\"X\",\"pid\":{pid},\"tid\":0,\"ts\":0.000,\"dur\":2000.000}}\n",
+                "]}}\n"
+            ),
+            pid = pid
+        )
+    );
+}
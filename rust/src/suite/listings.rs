@@ -0,0 +1,605 @@
+use std::{
+    arch::asm,
+    cell::{Cell, RefCell},
+    fs::File,
+    hint::black_box,
+    io::{self, Read},
+    mem::{self, MaybeUninit},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use aligned::{A64, Aligned};
+use asm::{alignment, load_store_ports, non_temporal_store, nuke_cache, prefetching, simd};
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro128PlusPlus;
+
+use crate::{rep_tester::units::Unit, write::RawAlloc};
+
+use super::{Case, Suite};
+
+// https://www.computerenhance.com/p/code-alignment
+pub fn code_alignment(suite: &mut Suite, iterations: u64) {
+    let alignments: [(&str, unsafe extern "C" fn(u64)); 8] = [
+        ("align_64", alignment::align_64),
+        ("misalign_1", alignment::misalign_1),
+        ("misalign_8", alignment::misalign_8),
+        ("misalign_16", alignment::misalign_16),
+        ("misalign_32", alignment::misalign_32),
+        ("misalign_48", alignment::misalign_48),
+        ("misalign_62", alignment::misalign_62),
+        ("misalign_63", alignment::misalign_63),
+    ];
+
+    for (name, ptr) in alignments {
+        suite.add(
+            Case::simple(name, iterations, move || unsafe {
+                ptr(iterations);
+            })
+            .unit(Unit::Iterations)
+            .precise(),
+        );
+    }
+}
+
+pub fn load_store_ports(suite: &mut Suite, loops: u64) {
+    let loads: [(&str, unsafe extern "C" fn(u64, *mut u64)); 6] = [
+        ("read_1", load_store_ports::read_1),
+        ("read_2", load_store_ports::read_2),
+        ("read_3", load_store_ports::read_3),
+        ("read_4", load_store_ports::read_4),
+        ("read_1x2", load_store_ports::read_1x2),
+        ("read_8x2", load_store_ports::read_8x2),
+    ];
+    for (name, ptr) in loads {
+        let mut mem = Box::new(0u64);
+        suite.add(
+            Case::simple(name, loops, move || unsafe {
+                ptr(loops, &mut *mem);
+            })
+            .unit(Unit::Iterations)
+            .precise(),
+        );
+    }
+
+    type StorePtr = unsafe extern "C" fn(u64, *mut u64, *mut u64, *mut u64, *mut u64);
+    let stores: [(&str, StorePtr); 4] = [
+        ("write_1", load_store_ports::write_1),
+        ("write_2", load_store_ports::write_2),
+        ("write_3", load_store_ports::write_3),
+        ("write_4", load_store_ports::write_4),
+    ];
+    for (name, ptr) in stores {
+        let mut mem = Box::new([0u64; 4]);
+        suite.add(
+            Case::simple(name, loops, move || unsafe {
+                let [a, b, c, d] = &mut *mem;
+                ptr(loops, a, b, c, d);
+            })
+            .unit(Unit::Iterations)
+            .precise(),
+        );
+    }
+}
+
+pub fn cache_size(suite: &mut Suite) {
+    const BUF_BITS: usize = 30;
+    const MIN_READ_BITS: usize = 8;
+    const BUF_SIZE: usize = 1 << BUF_BITS;
+
+    // mmap is lazy, listing cases doesn't touch the memory
+    let buf = Rc::new(RawAlloc::new(BUF_SIZE));
+
+    for i in MIN_READ_BITS..=BUF_BITS {
+        let name = if i >= 20 {
+            format!("cache_{}MB", 1 << (i - 20))
+        } else if i >= 10 {
+            format!("cache_{}kB", 1 << (i - 10))
+        } else {
+            format!("cache_{}B", 1 << i)
+        };
+        // 4 -> (1 << 4) -> 10000 -> 1111
+        let mask = (1 << i) - 1;
+        let buf = buf.clone();
+
+        suite.add(Case::simple(name, BUF_SIZE as u64, move || unsafe {
+            asm::cache::test_cache(BUF_SIZE as u64, mask, buf.as_u8_mut_ptr());
+        }));
+    }
+}
+
+pub fn nuke_l1(suite: &mut Suite, size: u64) {
+    assert!(size > 0);
+    assert!(size & (nuke_cache::L1_ITERATION_READ_BYTES as u64 - 1) == 0);
+
+    let buf = RawAlloc::new(nuke_cache::L1_NUKE_REQUIRED_MEMORY);
+    let iterations = size / nuke_cache::L1_ITERATION_READ_BYTES as u64;
+
+    suite.add(Case::simple("nuke l1", size, move || unsafe {
+        nuke_cache::nuke_l1(iterations, buf.as_mut_ptr() as *mut u64);
+    }));
+}
+
+pub fn non_temporal_store(suite: &mut Suite) {
+    const MIN_REP: usize = 16;
+    const MAX_REP: usize = 64;
+    const LEN: usize = 128 * 1024;
+
+    let src = Rc::new(RawAlloc::new(LEN));
+    let dst = Rc::new(RawAlloc::new(LEN * MAX_REP));
+
+    for i in MIN_REP.ilog2()..=MAX_REP.ilog2() {
+        let reps = 2_usize.pow(i);
+        let bytes = (LEN * reps) as u64;
+
+        let (src_ref, dst_ref) = (src.clone(), dst.clone());
+        suite.add(Case::simple(
+            format!("temporal 1x{}", reps),
+            bytes,
+            move || unsafe {
+                non_temporal_store::baseline_fill(
+                    LEN as u64,
+                    src_ref.as_u8_ptr(),
+                    reps as u64,
+                    dst_ref.as_u8_mut_ptr(),
+                );
+            },
+        ));
+
+        let (src_ref, dst_ref) = (src.clone(), dst.clone());
+        suite.add(
+            Case::simple(format!("non-temporal 1x{}", reps), bytes, move || unsafe {
+                non_temporal_store::non_temporal_fill(
+                    LEN as u64,
+                    src_ref.as_u8_ptr(),
+                    reps as u64,
+                    dst_ref.as_u8_mut_ptr(),
+                );
+            })
+            .compare_to(format!("temporal 1x{}", reps)),
+        );
+    }
+}
+
+// a loop of `iterations` with `reps` copies of the nop encoded by `bytes` in its body
+macro_rules! nop_loop {
+    ($bytes:literal x $reps:literal) => {
+        |iterations: u64| unsafe {
+            asm!(
+                "2:",
+                ".rept {reps}",
+                concat!(".byte ", $bytes),
+                ".endr",
+                "inc {in}",
+                "cmp {in}, {target}",
+                "jb 2b",
+                in = inout(reg) 0u64 => _,
+                target = in(reg) iterations,
+                reps = const $reps,
+                options(nostack)
+            )
+        }
+    };
+}
+
+type NopLoop = fn(iterations: u64);
+
+pub fn multinop_loops(suite: &mut Suite, iterations: u64) {
+    let loops: [(&str, NopLoop); 8] = [
+        ("nop3Bx1", nop_loop!("0x0F, 0x1F, 0x00" x 1)),
+        ("nop1Bx3", nop_loop!("0x90" x 3)),
+        ("nop3Bx3", nop_loop!("0x0F, 0x1F, 0x00" x 3)),
+        ("nop1Bx9", nop_loop!("0x90" x 9)),
+        (
+            "nop9Bx1",
+            nop_loop!("0x66, 0x0F, 0x1F, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00" x 1),
+        ),
+        (
+            "nop9Bx3",
+            nop_loop!("0x66, 0x0F, 0x1F, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00" x 3),
+        ),
+        (
+            "nop9Bx10",
+            nop_loop!("0x66, 0x0F, 0x1F, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00" x 10),
+        ),
+        (
+            "nop9Bx30",
+            nop_loop!("0x66, 0x0F, 0x1F, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00" x 30),
+        ),
+    ];
+
+    for (name, run) in loops {
+        suite.add(Case::simple(name, iterations, move || run(iterations)).unit(Unit::Iterations));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum BranchPattern {
+    Never,
+    Always,
+    Every(u8),
+    OSRandom,
+}
+
+impl BranchPattern {
+    fn name(self) -> String {
+        match self {
+            BranchPattern::Never => "BranchNever".to_string(),
+            BranchPattern::Always => "BranchAlways".to_string(),
+            BranchPattern::Every(n) => format!("BranchEvery{}", n),
+            BranchPattern::OSRandom => "BranchOSRandom".to_string(),
+        }
+    }
+
+    fn fill(self, bytes: &mut [u8]) -> io::Result<()> {
+        if self == BranchPattern::OSRandom {
+            let mut entropy = [0u8; 128];
+
+            // a byte per bit of entropy
+            for chunk in bytes.chunks_mut(entropy.len() * 8) {
+                let res = unsafe {
+                    libc::getrandom(entropy.as_mut_ptr() as *mut libc::c_void, entropy.len(), 0)
+                };
+                if res == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                for (i, value) in chunk.iter_mut().enumerate() {
+                    *value = (entropy[i >> 3] >> (i & 7)) & 1;
+                }
+            }
+            return Ok(());
+        }
+
+        for (i, value) in bytes.iter_mut().enumerate() {
+            *value = match self {
+                BranchPattern::Never => 0,
+                BranchPattern::Always => 1,
+                BranchPattern::Every(n) => (i % n as usize == 0).into(),
+                BranchPattern::OSRandom => unreachable!(),
+            };
+        }
+
+        Ok(())
+    }
+}
+
+fn jump_loop(slice: &mut [u8]) {
+    unsafe {
+        asm!(
+            r#"
+                xor {val:l}, {val:l}
+                xor {idx}, {idx}
+            2:
+                mov {val:l}, [{ptr} + {idx}]
+                test {val:l}, 1
+                jne 3f
+                # nop 3B
+                .byte 0x0F, 0x1F, 0x00
+            3:
+                inc {idx}
+                cmp {idx}, {amount}
+                jb 2b
+            "#,
+            idx = out(reg) _,
+            val = out(reg) _,
+            ptr = in(reg) slice.as_mut_ptr(),
+            amount = in(reg) (slice.len() as u64),
+            options(nostack)
+        );
+    };
+}
+
+pub fn jump_patterns(suite: &mut Suite, bytes: usize) {
+    let patterns = [
+        BranchPattern::Never,
+        BranchPattern::Always,
+        BranchPattern::Every(2),
+        BranchPattern::Every(3),
+        BranchPattern::Every(4),
+        BranchPattern::Every(8),
+        BranchPattern::OSRandom,
+    ];
+    // one buffer for every pattern, filled again only when another pattern ran in between
+    let buf = Rc::new(RefCell::new((None, vec![0u8; bytes])));
+
+    for pattern in patterns {
+        let (fill_buf, run_buf) = (buf.clone(), buf.clone());
+        suite.add(Case::new(
+            pattern.name(),
+            bytes as u64,
+            move || {
+                let (filled, bytes) = &mut *fill_buf.borrow_mut();
+                if *filled != Some(pattern) {
+                    pattern.fill(bytes).expect("getrandom must succeed");
+                    *filled = Some(pattern);
+                }
+            },
+            move |_| jump_loop(&mut run_buf.borrow_mut().1),
+        ));
+    }
+}
+
+type SimdPtr = unsafe extern "C" fn(iterations: u64, addr: *mut u64);
+
+fn simd_cases(suite: &mut Suite, loops: u64, ops: &[(&str, SimdPtr)]) {
+    for (name, ptr) in ops.iter().copied() {
+        let mut array: Box<Aligned<A64, [u64; 256]>> = Box::new(Aligned([0u64; 256]));
+        suite.add(
+            Case::simple(name, loops, move || unsafe {
+                ptr(loops, array.as_mut_ptr())
+            })
+            .unit(Unit::Iterations),
+        );
+    }
+}
+
+pub fn simd_load_width(suite: &mut Suite, loops: u64) {
+    simd_cases(
+        suite,
+        loops,
+        &[
+            ("read_4x3", simd::read_4x3),
+            ("read_8x3", simd::read_8x3),
+            ("read_16x3", simd::read_16x3),
+            ("read_32x3", simd::read_32x3),
+        ],
+    );
+}
+
+pub fn simd_load_lines(suite: &mut Suite, loops: u64) {
+    simd_cases(
+        suite,
+        loops,
+        &[
+            ("read_same_32x1", simd::read_same_32x1),
+            ("read_same_32x2", simd::read_same_32x2),
+            ("read_same_32x3", simd::read_same_32x3),
+            ("read_same_32x4", simd::read_same_32x4),
+        ],
+    );
+}
+
+pub fn simd_write_lines(suite: &mut Suite, loops: u64) {
+    simd_cases(
+        suite,
+        loops,
+        &[
+            ("write_32x1", simd::write_32x1),
+            ("write_32x2", simd::write_32x2),
+            ("write_32x3", simd::write_32x3),
+        ],
+    );
+}
+
+// 4 fixed-seed streams, so every run and both variants walk the same offsets
+fn packed_access_pattern(accesses: usize, size: usize) -> Vec<u32> {
+    assert!(accesses.is_multiple_of(4));
+    let mut streams: [Xoshiro128PlusPlus; 4] = std::array::from_fn(|idx| {
+        let mut seed = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+        seed[0] = idx as u8;
+        Xoshiro128PlusPlus::from_seed(seed)
+    });
+
+    let mut v = vec![0; accesses];
+    for chunk in v.chunks_exact_mut(4) {
+        for (value, rng) in chunk.iter_mut().zip(&mut streams) {
+            *value = rng.random_range(0..size as u32);
+        }
+    }
+
+    v
+}
+
+pub fn prefetching(suite: &mut Suite) {
+    const SIZE: usize = 512;
+    const BYTES_IN_MB: usize = 1024 * 1024;
+
+    let buf = Rc::new(RawAlloc::new(SIZE * BYTES_IN_MB));
+    // the pattern of the last size, both variants of a size share it
+    let pattern: Rc<RefCell<(u64, Vec<u32>)>> = Rc::new(RefCell::new((0, Vec::new())));
+    // every prefetching run must sum up the same as the plain one
+    let expected = Rc::new(Cell::new(None));
+
+    for i in 1..=SIZE.ilog2() {
+        let mbs: u64 = 2_u64.pow(i);
+        let len = mbs * BYTES_IN_MB as u64;
+        let plain = format!("no prefetching {}MB", mbs);
+
+        for prefetch in [false, true] {
+            let (setup_pattern, run_pattern) = (pattern.clone(), pattern.clone());
+            let (check_expected, buf) = (expected.clone(), buf.clone());
+
+            let case = Case::new(
+                match prefetch {
+                    true => format!("prefetching {}MB", mbs),
+                    false => plain.clone(),
+                },
+                len,
+                move || {
+                    let (pattern_len, pattern) = &mut *setup_pattern.borrow_mut();
+                    if *pattern_len != len {
+                        *pattern = packed_access_pattern(len as usize, SIZE * BYTES_IN_MB);
+                        *pattern_len = len;
+                    }
+                    0u64
+                },
+                move |result| unsafe {
+                    let offsets = run_pattern.borrow().1.as_ptr();
+                    let ptr = buf.as_ptr() as *const u64;
+                    *result = match prefetch {
+                        true => prefetching::prefetching(len, offsets, ptr),
+                        false => prefetching::no_prefetching(len, offsets, ptr),
+                    };
+                },
+            )
+            .check(move |result| match prefetch {
+                true => check_expected.get().is_none_or(|it| it == *result),
+                false => {
+                    check_expected.set(Some(*result));
+                    true
+                }
+            });
+
+            suite.add(match prefetch {
+                true => case.compare_to(plain.clone()),
+                false => case,
+            });
+        }
+    }
+}
+
+// https://www.computerenhance.com/p/reading-files-with-rust, reads of the whole `path`
+pub fn file_reads(suite: &mut Suite, path: &Path) -> io::Result<()> {
+    let len = path.metadata()?.len();
+    let path: Rc<PathBuf> = Rc::new(path.to_owned());
+    let open = move || File::open(&*path).expect("file must stay readable");
+
+    const I64_SIZE: usize = size_of::<i64>();
+    let size = 700 * 1024 * 1024 / I64_SIZE;
+    suite.add(
+        Case::new(
+            "page_faults_check",
+            (size * I64_SIZE) as u64,
+            move || {
+                let seed = rand::random::<i64>();
+                (seed, Box::<[i64]>::new_uninit_slice(size))
+            },
+            move |(seed, arr)| {
+                for (i, value) in arr.iter_mut().enumerate() {
+                    *value = MaybeUninit::new(*seed * (i as i64 + 1) * 8);
+                }
+            },
+        )
+        .check(move |(_, arr)| arr.len() == size)
+        .teardown(|(_, arr)| {
+            black_box(unsafe { arr.assume_init() }.iter().sum::<i64>());
+        }),
+    );
+
+    // the string is reused between runs, so no allocation is measured
+    let reused = Rc::new(RefCell::new(String::with_capacity(len as usize)));
+    let (take, give_back) = (reused.clone(), reused);
+    let opener = open.clone();
+    suite.add(
+        Case::new(
+            "File::read_to_string",
+            len,
+            move || {
+                let mut json = mem::take(&mut *take.borrow_mut());
+                json.clear();
+                (json, opener())
+            },
+            |(json, file)| {
+                file.read_to_string(json).unwrap();
+            },
+        )
+        .check(move |(json, _)| json.len() == len as usize)
+        .teardown(move |(json, _)| *give_back.borrow_mut() = json),
+    );
+
+    let opener = open.clone();
+    suite.add(
+        Case::new(
+            "File::read_to_string + malloc",
+            len,
+            move || (String::new(), opener()),
+            |(json, file)| {
+                file.read_to_string(json).unwrap();
+            },
+        )
+        .check(move |(json, _)| json.len() == len as usize),
+    );
+
+    let reused = Rc::new(RefCell::new(Vec::with_capacity(len as usize + 1)));
+    let (take, give_back) = (reused.clone(), reused);
+    let opener = open.clone();
+    suite.add(
+        Case::new(
+            "File::read",
+            len,
+            move || {
+                let mut json = mem::take(&mut *take.borrow_mut());
+                json.clear();
+                (json, opener())
+            },
+            |(json, file)| {
+                file.read_to_end(json).unwrap();
+            },
+        )
+        .check(move |(json, _)| json.len() == len as usize)
+        .teardown(move |(json, _)| *give_back.borrow_mut() = json),
+    );
+
+    let opener = open.clone();
+    suite.add(
+        Case::new(
+            "loop { File::read } + malloc (4K)",
+            len,
+            move || {
+                let json = RawAlloc::new(len as usize);
+                unsafe { libc::madvise(json.as_mut_ptr(), json.size(), libc::MADV_NOHUGEPAGE) };
+                (json, opener())
+            },
+            |(json, file)| {
+                let buf = json.as_u8_slice_mut();
+                let mut read = 0;
+                loop {
+                    let cur_read = file.read(&mut buf[read..]).unwrap();
+                    read += cur_read;
+                    if cur_read == 0 {
+                        break;
+                    }
+                }
+            },
+        )
+        .check(move |(json, _)| json.size() == len as usize),
+    );
+
+    let opener = open.clone();
+    suite.add(
+        Case::new(
+            "File::read_exact + malloc aligned",
+            len,
+            move || (RawAlloc::new(round_up_to_2mb(len as usize)), opener()),
+            move |(json, file)| {
+                file.read_exact(&mut json.as_u8_slice_mut()[..len as usize])
+                    .unwrap();
+            },
+        )
+        .check(move |(json, _)| json.as_u8_slice_mut()[len as usize - 2] != 0),
+    );
+
+    suite.add(
+        Case::new(
+            "File::read_exact + malloc (auto)",
+            len,
+            move || (RawAlloc::new(len as usize), open()),
+            |(json, file)| {
+                file.read_exact(json.as_u8_slice_mut()).unwrap();
+            },
+        )
+        .check(move |(json, _)| json.size() == len as usize),
+    );
+
+    Ok(())
+}
+
+fn round_up_to_2mb(x: usize) -> usize {
+    const TWO_MB: usize = 1 << 21;
+    (x + TWO_MB - 1) & !(TWO_MB - 1)
+}
+
+#[test]
+fn branch_patterns_and_nop_loops() {
+    let mut bytes = [0u8; 9];
+    BranchPattern::Every(3).fill(&mut bytes).unwrap();
+    assert_eq!(bytes, [1, 0, 0, 1, 0, 0, 1, 0, 0]);
+
+    let mut random = vec![2u8; 3000];
+    BranchPattern::OSRandom.fill(&mut random).unwrap();
+    assert!(random.iter().all(|it| *it <= 1));
+    jump_loop(&mut random);
+
+    let nop9x30: NopLoop = nop_loop!("0x66, 0x0F, 0x1F, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00" x 30);
+    nop9x30(1000);
+}
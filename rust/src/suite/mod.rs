@@ -0,0 +1,496 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, Write, stdout},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    core_affinity, rep_run,
+    rep_tester::{
+        RepTester,
+        compare::{Comparison, RunSnapshot, print_comparisons},
+        config::ConfigError,
+        error::RepTestError,
+        report::{CsvReporter, JsonLinesReporter},
+        units::Unit,
+    },
+};
+
+pub mod listings;
+
+pub struct Case<'a, S> {
+    name: String,
+    bytes: u64,
+    unit: Unit,
+    precise: bool,
+    compare_to: Option<String>,
+    setup: Box<dyn FnMut() -> S + 'a>,
+    block: Box<dyn FnMut(&mut S) + 'a>,
+    check: Box<dyn FnMut(&S) -> bool + 'a>,
+    teardown: Box<dyn FnMut(S) + 'a>,
+}
+
+impl<'a> Case<'a, ()> {
+    pub fn simple(name: impl Into<String>, bytes: u64, mut block: impl FnMut() + 'a) -> Self {
+        Case::new(name, bytes, || (), move |_| block())
+    }
+}
+
+// setup/check/teardown run around every single measured block, outside of the measurement
+impl<'a, S: 'a> Case<'a, S> {
+    pub fn new(
+        name: impl Into<String>,
+        bytes: u64,
+        setup: impl FnMut() -> S + 'a,
+        block: impl FnMut(&mut S) + 'a,
+    ) -> Self {
+        Case {
+            name: name.into(),
+            bytes,
+            unit: Unit::Bytes,
+            precise: false,
+            compare_to: None,
+            setup: Box::new(setup),
+            block: Box::new(block),
+            check: Box::new(|_| true),
+            teardown: Box::new(drop),
+        }
+    }
+
+    // what `bytes` counts, for blocks which loop a given number of times
+    pub fn unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    // serialized timer reads, see `RepConfig::precise_reads`
+    pub fn precise(mut self) -> Self {
+        self.precise = true;
+        self
+    }
+
+    // A/B against an earlier case of the same round, printed after every round
+    pub fn compare_to(mut self, baseline: impl Into<String>) -> Self {
+        self.compare_to = Some(baseline.into());
+        self
+    }
+
+    pub fn check(mut self, check: impl FnMut(&S) -> bool + 'a) -> Self {
+        self.check = Box::new(check);
+        self
+    }
+
+    pub fn teardown(mut self, teardown: impl FnMut(S) + 'a) -> Self {
+        self.teardown = Box::new(teardown);
+        self
+    }
+}
+
+trait Runnable {
+    fn name(&self) -> &str;
+    fn compare_to(&self) -> Option<&str>;
+    fn run(&mut self, tester: &mut RepTester) -> Result<(), RepTestError>;
+}
+
+impl<S> Runnable for Case<'_, S> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn compare_to(&self) -> Option<&str> {
+        self.compare_to.as_deref()
+    }
+
+    fn run(&mut self, tester: &mut RepTester) -> Result<(), RepTestError> {
+        let precise_reads = tester.config.precise_reads;
+        tester.config.precise_reads |= self.precise;
+
+        let result = rep_run!(
+            tester,
+            name = &self.name,
+            len = self.bytes,
+            unit = self.unit,
+            before = {
+                let mut state = (self.setup)();
+            },
+            block = {
+                (self.block)(&mut state);
+            },
+            check = { (self.check)(&state) },
+            after_run = {
+                (self.teardown)(state);
+            }
+        );
+
+        tester.config.precise_reads = precise_reads;
+        result
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    JsonLines,
+    Csv,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct RunOptions {
+    pub list: bool,
+    pub filter: Option<String>,
+    // None repeats forever, as listings used to
+    pub rounds: Option<u64>,
+    // wall time budget for the whole suite, checked between cases
+    pub duration: Option<Duration>,
+    pub report: Option<String>,
+    pub no_pin: bool,
+}
+
+impl RunOptions {
+    pub const USAGE: &'static str = "[--list] [--filter <glob>] [--rounds <n>] [--duration <secs>] [--report <file.jsonl|file.csv>] [--no-pin]";
+
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<RunOptions, String> {
+        let mut options = RunOptions::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("{} expects a value", name))
+            };
+
+            match arg.as_str() {
+                "--list" => options.list = true,
+                "--no-pin" => options.no_pin = true,
+                "--filter" => options.filter = Some(value("--filter")?),
+                "--rounds" => {
+                    options.rounds = Some(
+                        value("--rounds")?
+                            .replace('_', "")
+                            .parse()
+                            .map_err(|_| "--rounds expects an integer".to_string())?,
+                    )
+                }
+                "--duration" => {
+                    let secs: f64 = value("--duration")?
+                        .parse()
+                        .map_err(|_| "--duration expects seconds".to_string())?;
+                    options.duration = Some(Duration::from_secs_f64(secs));
+                }
+                "--report" => options.report = Some(value("--report")?),
+                other => return Err(format!("unknown argument '{}'", other)),
+            }
+        }
+
+        Ok(options)
+    }
+
+    fn report_format(path: &str) -> ReportFormat {
+        if path.ends_with(".csv") {
+            ReportFormat::Csv
+        } else {
+            ReportFormat::JsonLines
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SuiteError {
+    Io(io::Error),
+    Config(ConfigError),
+    // every case which failed in any round, with its first error
+    Failed(Vec<(String, RepTestError)>),
+}
+
+impl From<io::Error> for SuiteError {
+    fn from(error: io::Error) -> Self {
+        SuiteError::Io(error)
+    }
+}
+
+impl From<ConfigError> for SuiteError {
+    fn from(error: ConfigError) -> Self {
+        SuiteError::Config(error)
+    }
+}
+
+impl fmt::Display for SuiteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SuiteError::Io(error) => write!(f, "{}", error),
+            SuiteError::Config(error) => write!(f, "{}", error),
+            SuiteError::Failed(failures) => {
+                write!(f, "{} case(s) failed:", failures.len())?;
+                for (name, error) in failures {
+                    write!(f, "\n  {}: {}", name, error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for SuiteError {}
+
+impl SuiteError {
+    // regressions alone keep their exit code, anything else is a plain failure
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SuiteError::Failed(failures)
+                if failures
+                    .iter()
+                    .all(|(_, it)| *it == RepTestError::Regressed) =>
+            {
+                RepTestError::Regressed.exit_code()
+            }
+            _ => 1,
+        }
+    }
+}
+
+pub struct Suite<'a> {
+    cases: Vec<Box<dyn Runnable + 'a>>,
+}
+
+impl<'a> Suite<'a> {
+    pub fn new() -> Suite<'a> {
+        Suite { cases: Vec::new() }
+    }
+
+    pub fn add<S: 'a>(&mut self, case: Case<'a, S>) -> &mut Self {
+        self.cases.push(Box::new(case));
+        self
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.cases.iter().map(|it| it.name())
+    }
+
+    pub fn matching<'s>(&'s self, filter: Option<&'s str>) -> impl Iterator<Item = &'s str> {
+        self.names()
+            .filter(move |name| filter.is_none_or(|filter| glob_matches(filter, name)))
+    }
+
+    // failed cases don't stop the rounds, they are returned once all of them are done
+    pub fn run(&mut self, options: &RunOptions) -> Result<(), SuiteError> {
+        let filter = options.filter.as_deref();
+
+        if options.list {
+            let mut out = stdout();
+            for name in self.matching(filter) {
+                writeln!(out, "{}", name)?;
+            }
+            return Ok(());
+        }
+
+        // resolved up front, a filter matching nothing would otherwise spin through empty rounds
+        let selected: Vec<usize> = (0..self.cases.len())
+            .filter(|idx| filter.is_none_or(|filter| glob_matches(filter, self.cases[*idx].name())))
+            .collect();
+        if selected.is_empty() {
+            return Err(SuiteError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("no case matches {}", filter.unwrap_or("*")),
+            )));
+        }
+
+        if !options.no_pin {
+            core_affinity::set_single_core()?;
+        }
+        let mut tester = RepTester::new()?;
+        if let Some(path) = &options.report {
+            let path_ref = Path::new(path);
+            match RunOptions::report_format(path) {
+                ReportFormat::Csv => tester.set_reporter(CsvReporter::create(path_ref)?),
+                ReportFormat::JsonLines => {
+                    tester.set_reporter(JsonLinesReporter::create(path_ref)?)
+                }
+            }
+        }
+
+        let baselines: Vec<String> = selected
+            .iter()
+            .filter_map(|idx| self.cases[*idx].compare_to())
+            .map(String::from)
+            .collect();
+        tester.retain_samples |= !baselines.is_empty();
+        let mut snapshots: HashMap<String, RunSnapshot> = HashMap::new();
+        let mut comparisons: Vec<Comparison> = Vec::new();
+        let mut failures: Vec<(String, RepTestError)> = Vec::new();
+
+        let deadline = options.duration.map(|it| Instant::now() + it);
+        let mut round = 0;
+        'rounds: while options.rounds.is_none_or(|rounds| round < rounds) {
+            for idx in &selected {
+                if deadline.is_some_and(|it| Instant::now() >= it) || tester.budget_exhausted() {
+                    break 'rounds;
+                }
+
+                let case = &mut self.cases[*idx];
+                // a failed case is reported and the rest of the suite keeps going
+                if let Err(error) = case.run(&mut tester) {
+                    eprintln!("{}: {}", case.name(), error);
+                    if !failures.iter().any(|(name, _)| name == case.name()) {
+                        failures.push((case.name().to_string(), error));
+                    }
+                    continue;
+                }
+
+                if let Some(baseline) = case.compare_to().and_then(|it| snapshots.get(it)) {
+                    comparisons.extend(tester.compare(baseline));
+                }
+                if baselines.iter().any(|it| it == case.name()) {
+                    snapshots.extend(tester.snapshot().map(|it| (it.name.clone(), it)));
+                }
+            }
+            if !comparisons.is_empty() {
+                print_comparisons(&mut stdout(), &comparisons)?;
+                comparisons.clear();
+            }
+            round += 1;
+        }
+
+        match failures.is_empty() {
+            true => Ok(()),
+            false => Err(SuiteError::Failed(failures)),
+        }
+    }
+
+    // for binaries, any error is printed and exits the process with a non-zero code
+    pub fn run_or_exit(&mut self, options: &RunOptions) {
+        if let Err(error) = self.run(options) {
+            eprintln!("{}", error);
+            std::process::exit(error.exit_code());
+        }
+    }
+
+    // entry point for binaries: options come from the process arguments
+    pub fn run_main(&mut self) {
+        let options = match RunOptions::from_args(std::env::args().skip(1)) {
+            Ok(options) => options,
+            Err(message) => {
+                eprintln!("{}\nusage: {}", message, RunOptions::USAGE);
+                std::process::exit(1);
+            }
+        };
+
+        self.run_or_exit(&options);
+    }
+}
+
+impl Default for Suite<'_> {
+    fn default() -> Self {
+        Suite::new()
+    }
+}
+
+// `*` matches any sequence, `?` any single char
+pub fn glob_matches(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+
+    let (mut p, mut v) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while v < value.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == value[v]) {
+            p += 1;
+            v += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, v));
+            p += 1;
+        } else if let Some((star_p, star_v)) = backtrack {
+            p = star_p + 1;
+            v = star_v + 1;
+            backtrack = Some((star_p, star_v + 1));
+        } else {
+            return false;
+        }
+    }
+    while p < pattern.len() && pattern[p] == '*' {
+        p += 1;
+    }
+
+    p == pattern.len()
+}
+
+#[test]
+fn glob() {
+    assert!(glob_matches("cache_*", "cache_32kB"));
+    assert!(glob_matches("*kB", "cache_32kB"));
+    assert!(glob_matches("*_3?kB", "cache_32kB"));
+    assert!(glob_matches("*", ""));
+    assert!(glob_matches("misalign_*", "misalign_1"));
+    assert!(!glob_matches("misalign_*", "align_64"));
+    assert!(!glob_matches("cache_?", "cache_32"));
+    assert!(glob_matches("a*b*c", "axxbyyc"));
+    assert!(!glob_matches("a*b*c", "axxbyy"));
+}
+
+#[test]
+fn run_options() {
+    let args = [
+        "--filter",
+        "cache_*",
+        "--rounds",
+        "1_000",
+        "--duration",
+        "1.5",
+        "--list",
+    ];
+    let options = RunOptions::from_args(args.map(String::from)).unwrap();
+
+    assert_eq!(options.filter.as_deref(), Some("cache_*"));
+    assert_eq!(options.rounds, Some(1000));
+    assert_eq!(options.duration, Some(Duration::from_millis(1500)));
+    assert!(options.list);
+    assert!(RunOptions::from_args(["--rounds".to_string()]).is_err());
+    assert!(RunOptions::from_args(["--what".to_string()]).is_err());
+}
+
+#[test]
+fn filters_registered_cases() {
+    let mut suite = Suite::new();
+    suite
+        .add(Case::simple("cache_16kB", 16, || {}))
+        .add(Case::simple("cache_32kB", 32, || {}))
+        .add(Case::new("read", 1, || vec![0u8; 1], |it| it[0] += 1).check(|it| it[0] == 1));
+
+    let names: Vec<&str> = suite.matching(Some("cache_*")).collect();
+    assert_eq!(names, ["cache_16kB", "cache_32kB"]);
+    assert_eq!(suite.matching(None).count(), 3);
+}
+
+#[test]
+fn rejects_filter_without_cases() {
+    let mut suite = Suite::new();
+    suite.add(Case::simple("cache_16kB", 16, || {}));
+
+    let options = RunOptions {
+        filter: Some("misalign_*".to_string()),
+        ..Default::default()
+    };
+    let error = suite.run(&options).unwrap_err();
+    assert!(matches!(&error, SuiteError::Io(it) if it.kind() == io::ErrorKind::InvalidInput));
+    assert_eq!(error.to_string(), "no case matches misalign_*");
+}
+
+#[test]
+fn returns_failed_checks() {
+    let mut suite = Suite::new();
+    suite.add(Case::new("broken", 1, || 0u8, |it| *it += 1).check(|_| false));
+
+    let options = RunOptions {
+        rounds: Some(1),
+        no_pin: true,
+        ..Default::default()
+    };
+    match suite.run(&options) {
+        Err(SuiteError::Failed(failures)) => assert_eq!(
+            failures,
+            [("broken".to_string(), RepTestError::CheckFailed { run: 1 })]
+        ),
+        other => panic!("expected a failed case, got {:?}", other),
+    }
+}
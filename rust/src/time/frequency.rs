@@ -0,0 +1,156 @@
+use std::{sync::OnceLock, time::Duration};
+
+use super::{TimeMeasurer, spin_frequency};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrequencyMethod {
+    // crystal clock times the TSC ratio
+    Cpuid15,
+    // processor base frequency, nominal
+    Cpuid16,
+    // conversion the kernel's `tsc` clocksource uses
+    Kernel,
+    CounterRegister,
+    // clock_gettime counts nanoseconds
+    OsClock,
+    Calibrated,
+}
+
+impl FrequencyMethod {
+    pub fn name(self) -> &'static str {
+        match self {
+            FrequencyMethod::Cpuid15 => "CPUID 0x15",
+            FrequencyMethod::Cpuid16 => "CPUID 0x16",
+            FrequencyMethod::Kernel => "kernel tsc clocksource",
+            FrequencyMethod::CounterRegister => "CNTFRQ_EL0",
+            FrequencyMethod::OsClock => "clock_gettime",
+            FrequencyMethod::Calibrated => "spin calibration",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockFrequency {
+    pub hz: u64,
+    pub method: FrequencyMethod,
+}
+
+impl ClockFrequency {
+    pub const OS_CLOCK: ClockFrequency = ClockFrequency {
+        hz: 1_000_000_000,
+        method: FrequencyMethod::OsClock,
+    };
+}
+
+// rate of the hardware counter behind `TimeMeasurer`, RDTSC and RDTSCP,
+// asked once per process and only spin calibrated when nothing reports it
+pub fn counter_frequency(read: impl Fn() -> u64, max_estimation_time: Duration) -> ClockFrequency {
+    static FREQUENCY: OnceLock<ClockFrequency> = OnceLock::new();
+
+    *FREQUENCY.get_or_init(|| {
+        known_counter_frequency().unwrap_or_else(|| ClockFrequency {
+            hz: spin_frequency(read, max_estimation_time),
+            method: FrequencyMethod::Calibrated,
+        })
+    })
+}
+
+impl TimeMeasurer {
+    pub fn clock_frequency(&self, max_estimation_time: Duration) -> ClockFrequency {
+        if self.uses_os_clock() {
+            return ClockFrequency::OS_CLOCK;
+        }
+        counter_frequency(|| self.clocks_now(), max_estimation_time)
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(all(target_arch = "aarch64", feature = "timing_mac_os_cycles"))] {
+        // core cycles, they follow the current clock speed
+        fn known_counter_frequency() -> Option<ClockFrequency> {
+            None
+        }
+    } else if #[cfg(target_arch = "aarch64")] {
+        fn known_counter_frequency() -> Option<ClockFrequency> {
+            use std::arch::asm;
+
+            let mut hz: u64;
+            unsafe { asm!("mrs {hz}, CNTFRQ_EL0", hz = out(reg) hz) };
+
+            (hz != 0).then_some(ClockFrequency {
+                hz,
+                method: FrequencyMethod::CounterRegister,
+            })
+        }
+    } else if #[cfg(target_arch = "x86_64")] {
+        #[allow(unused_unsafe)]
+        fn known_counter_frequency() -> Option<ClockFrequency> {
+            use core::arch::x86_64::__cpuid;
+
+            let max_leaf = unsafe { __cpuid(0) }.eax;
+            let leaf_15 = (max_leaf >= 0x15).then(|| unsafe { __cpuid(0x15) });
+            let leaf_16 = (max_leaf >= 0x16).then(|| unsafe { __cpuid(0x16) });
+            let known = |hz: Option<u64>, method| hz.map(|hz| ClockFrequency { hz, method });
+
+            known(
+                leaf_15.and_then(|it| crystal_frequency(it.eax, it.ebx, it.ecx)),
+                FrequencyMethod::Cpuid15,
+            )
+            .or_else(|| {
+                known(
+                    crate::perf_event::kernel_tsc_frequency(),
+                    FrequencyMethod::Kernel,
+                )
+            })
+            .or_else(|| {
+                known(
+                    leaf_16.and_then(|it| base_frequency(it.eax)),
+                    FrequencyMethod::Cpuid16,
+                )
+            })
+        }
+    } else {
+        fn known_counter_frequency() -> Option<ClockFrequency> {
+            None
+        }
+    }
+}
+
+// leaf 0x15: EAX denominator, EBX numerator of the TSC/crystal ratio, ECX crystal Hz,
+// some parts leave the crystal out, those fall through to the other sources
+#[allow(dead_code)]
+fn crystal_frequency(denominator: u32, numerator: u32, crystal_hz: u32) -> Option<u64> {
+    if denominator == 0 || numerator == 0 || crystal_hz == 0 {
+        return None;
+    }
+
+    Some(crystal_hz as u64 * numerator as u64 / denominator as u64)
+}
+
+// leaf 0x16: EAX base frequency in MHz
+#[allow(dead_code)]
+fn base_frequency(mhz: u32) -> Option<u64> {
+    let mhz = mhz & 0xFFFF;
+    (mhz != 0).then_some(mhz as u64 * 1_000_000)
+}
+
+#[test]
+fn reads_cpuid_leaves() {
+    // 38.4 MHz crystal with a 2:125 ratio
+    assert_eq!(crystal_frequency(2, 125, 38_400_000), Some(2_400_000_000));
+    assert_eq!(crystal_frequency(2, 176, 0), None);
+    assert_eq!(crystal_frequency(0, 0, 0), None);
+
+    assert_eq!(base_frequency(2100), Some(2_100_000_000));
+    assert_eq!(base_frequency(0), None);
+}
+
+#[test]
+fn caches_counter_frequency() {
+    let measurer = TimeMeasurer::init().unwrap();
+    let first = measurer.clock_frequency(Duration::from_millis(10));
+    let second = measurer.clock_frequency(Duration::from_millis(10));
+
+    assert_eq!(first, second);
+    assert!(first.hz > 0);
+}
@@ -1,5 +1,31 @@
 use std::{arch::asm, time::Duration};
 
+pub mod frequency;
+pub mod quality;
+pub mod timer;
+
+// nanoseconds, used by `timing_os` and as the fallback for an unreliable TSC
+#[cfg(unix)]
+#[allow(dead_code)]
+fn os_clock_now() -> u64 {
+    use libc::{clock_gettime, timespec};
+
+    let mut spec = timespec {
+        tv_nsec: 0,
+        tv_sec: 0,
+    };
+    #[cfg(target_vendor = "apple")]
+    const CLOCK_ID: libc::clockid_t = libc::CLOCK_UPTIME_RAW;
+    #[cfg(not(target_vendor = "apple"))]
+    const CLOCK_ID: libc::clockid_t = libc::CLOCK_MONOTONIC;
+    let code = unsafe { clock_gettime(CLOCK_ID, &mut spec) };
+    assert!(code == 0);
+
+    const NSEC_PER_SEC: u64 = 1_000_000_000;
+
+    (((spec.tv_sec as u64) & 0xFFFF_FFFF) * NSEC_PER_SEC) + (spec.tv_nsec as u64)
+}
+
 cfg_if::cfg_if! {
     if #[cfg(all(target_arch = "aarch64", feature="timing_mac_os_cycles"))] {
         use libc::{c_int};
@@ -65,9 +91,18 @@ cfg_if::cfg_if! {
                     self.counters.cycles
                 }
             }
+            // a library call, nothing to serialize
+            pub fn clocks_start(&self) -> u64 {
+                self.clocks_now()
+            }
+            pub fn clocks_end(&self) -> u64 {
+                self.clocks_now()
+            }
+            pub fn uses_os_clock(&self) -> bool {
+                false
+            }
         }
     } else if #[cfg(feature="timing_os")] {
-        use libc::{CLOCK_MONOTONIC, clock_gettime, timespec};
         pub struct TimeMeasurer;
 
         impl TimeMeasurer {
@@ -75,21 +110,16 @@ cfg_if::cfg_if! {
                 Some(TimeMeasurer{})
             }
             pub fn clocks_now(&self) -> u64 {
-                let mut spec = timespec {tv_nsec: 0, tv_sec: 0};
-                #[cfg(target_vendor = "apple")]
-                const CLOCK_ID: libc::clockid_t = libc::CLOCK_UPTIME_RAW;
-                #[cfg(not(target_vendor = "apple"))]
-                const CLOCK_ID: libc::clockid_t = libc::CLOCK_MONOTONIC;
-                let code = unsafe {
-                     clock_gettime(CLOCK_ID, &mut spec)
-                };
-                assert!(code == 0);
-
-                const NSEC_PER_SEC: u64 = 1_000_000_000;
-
-                let res = (((spec.tv_sec as u64) & 0xFFFF_FFFF) * NSEC_PER_SEC) + (spec.tv_nsec as u64);
-
-                res
+                os_clock_now()
+            }
+            pub fn clocks_start(&self) -> u64 {
+                os_clock_now()
+            }
+            pub fn clocks_end(&self) -> u64 {
+                os_clock_now()
+            }
+            pub fn uses_os_clock(&self) -> bool {
+                true
             }
         }
     } else if #[cfg(all(target_arch = "aarch64", feature="timing_low_level"))] {
@@ -108,19 +138,72 @@ cfg_if::cfg_if! {
                     now
                 }
             }
+            // ISB keeps the counter read from being done ahead of earlier instructions
+            #[inline(always)]
+            pub fn clocks_start(&self) -> u64 {
+                unsafe {
+                    let now: u64;
+                    asm!("isb", "mrs {now}, CNTVCT_EL0", now = out(reg) now);
+
+                    now
+                }
+            }
+            #[inline(always)]
+            pub fn clocks_end(&self) -> u64 {
+                self.clocks_start()
+            }
+            pub fn uses_os_clock(&self) -> bool {
+                false
+            }
         }
     } else if #[cfg(feature="timing_low_level")] {
-        pub struct TimeMeasurer;
+        pub struct TimeMeasurer {
+            // RDTSC failed the `quality` checks, always predicted so it's free
+            os_clock: bool,
+        }
         impl TimeMeasurer {
             pub fn init() -> Option<TimeMeasurer> {
-                Some(TimeMeasurer {})
+                Some(TimeMeasurer {
+                    os_clock: quality::use_os_clock(),
+                })
             }
 
+            #[inline(always)]
             pub fn clocks_now(&self) -> u64 {
+                if self.os_clock {
+                    return os_clock_now();
+                }
+                unsafe {
+                    core::arch::x86_64::_rdtsc()
+                }
+            }
+            // `lfence; rdtsc`, the block can't start before the read
+            #[inline(always)]
+            pub fn clocks_start(&self) -> u64 {
+                if self.os_clock {
+                    return os_clock_now();
+                }
                 unsafe {
+                    core::arch::x86_64::_mm_lfence();
                     core::arch::x86_64::_rdtsc()
                 }
             }
+            // `rdtscp; lfence`, waits for the block and keeps what follows out of it
+            #[inline(always)]
+            pub fn clocks_end(&self) -> u64 {
+                if self.os_clock {
+                    return os_clock_now();
+                }
+                let mut aux = 0;
+                unsafe {
+                    let now = core::arch::x86_64::__rdtscp(&mut aux);
+                    core::arch::x86_64::_mm_lfence();
+                    now
+                }
+            }
+            pub fn uses_os_clock(&self) -> bool {
+                self.os_clock
+            }
         }
     } else {
         panic!("no matching options")
@@ -129,20 +212,25 @@ cfg_if::cfg_if! {
 
 impl TimeMeasurer {
     pub fn detect_clock_frequency(&self, max_estimation_time: Duration) -> u64 {
-        use std::time::Instant;
+        spin_frequency(|| self.clocks_now(), max_estimation_time)
+    }
+}
 
-        let clocks_at_start = self.clocks_now();
-        let start_instant = Instant::now();
-        loop {
-            if start_instant.elapsed() >= max_estimation_time {
-                break;
-            }
+// busy waits against `Instant`, ticks of `read` per second
+pub fn spin_frequency(read: impl Fn() -> u64, max_estimation_time: Duration) -> u64 {
+    use std::time::Instant;
+
+    let clocks_at_start = read();
+    let start_instant = Instant::now();
+    loop {
+        if start_instant.elapsed() >= max_estimation_time {
+            break;
         }
-        let clocks_at_end = self.clocks_now();
+    }
+    let clocks_at_end = read();
 
-        let clocks_delta = clocks_at_end - clocks_at_start;
-        let amount = (clocks_delta as f64) / max_estimation_time.as_secs_f64();
+    let clocks_delta = clocks_at_end - clocks_at_start;
+    let amount = (clocks_delta as f64) / max_estimation_time.as_secs_f64();
 
-        amount as u64
-    }
+    amount as u64
 }
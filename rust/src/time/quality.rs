@@ -0,0 +1,252 @@
+use std::{env, fs, sync::OnceLock};
+
+use super::TimeMeasurer;
+
+// what the CPU and kernel say about the TSC, None where it couldn't be read
+#[derive(Debug, Clone, PartialEq)]
+pub struct TscInfo {
+    // CPUID 0x8000_0007 EDX bit 8, constant rate through P-, C- and T-states
+    pub invariant_tsc: Option<bool>,
+    pub constant_tsc: Option<bool>,
+    pub nonstop_tsc: Option<bool>,
+    pub tsc_known_freq: Option<bool>,
+    // informational only, VM guests use kvm-clock and the like over an invariant TSC
+    pub clocksource: Option<String>,
+}
+
+// keeps RDTSC even when the checks fail, e.g. VMs which hide the CPUID bit
+pub const FORCE_TSC_ENV: &str = "TIME_FORCE_TSC";
+
+impl TscInfo {
+    pub fn detect() -> TscInfo {
+        let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok();
+        let flag = |name: &str| cpuinfo.as_deref().map(|it| has_cpu_flag(it, name));
+
+        TscInfo {
+            invariant_tsc: cpuid_invariant_tsc(),
+            constant_tsc: flag("constant_tsc"),
+            nonstop_tsc: flag("nonstop_tsc"),
+            tsc_known_freq: flag("tsc_known_freq"),
+            clocksource: fs::read_to_string(
+                "/sys/devices/system/clocksource/clocksource0/current_clocksource",
+            )
+            .ok()
+            .map(|it| it.trim().to_string()),
+        }
+    }
+
+    // reasons not to trust RDTSC, unknowns are not counted against it
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.invariant_tsc == Some(false) {
+            problems.push("CPUID reports no invariant TSC".to_string());
+        }
+        if self.constant_tsc == Some(false) {
+            problems.push("/proc/cpuinfo has no constant_tsc flag".to_string());
+        }
+        if self.nonstop_tsc == Some(false) {
+            problems.push("/proc/cpuinfo has no nonstop_tsc flag".to_string());
+        }
+
+        problems
+    }
+
+    // worth knowing, but not a reason to leave RDTSC
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        if let Some(clocksource) = self.clocksource.as_ref().filter(|it| *it != "tsc") {
+            warnings.push(format!(
+                "kernel clocksource is '{}', not 'tsc'",
+                clocksource
+            ));
+        }
+        // the kernel had to calibrate it too, so CPUID likely has no frequency either
+        if self.tsc_known_freq == Some(false) {
+            warnings.push(
+                "/proc/cpuinfo has no tsc_known_freq flag, the TSC frequency may be spin calibrated"
+                    .to_string(),
+            );
+        }
+
+        warnings
+    }
+}
+
+pub fn tsc_info() -> &'static TscInfo {
+    static INFO: OnceLock<TscInfo> = OnceLock::new();
+    INFO.get_or_init(TscInfo::detect)
+}
+
+// decided once per process, warns on stderr the first time RDTSC is rejected
+pub(super) fn use_os_clock() -> bool {
+    static USE_OS_CLOCK: OnceLock<bool> = OnceLock::new();
+
+    *USE_OS_CLOCK.get_or_init(|| {
+        let info = tsc_info();
+        for warning in info.warnings() {
+            eprintln!("note: {}", warning);
+        }
+
+        let problems = info.problems();
+        if problems.is_empty() {
+            return false;
+        }
+
+        let forced = env::var_os(FORCE_TSC_ENV).is_some();
+        eprintln!(
+            "warning: RDTSC is unreliable ({}), {}",
+            problems.join("; "),
+            match forced {
+                true => "kept because of TIME_FORCE_TSC",
+                false => "falling back to clock_gettime, set TIME_FORCE_TSC=1 to keep it",
+            }
+        );
+
+        !forced
+    })
+}
+
+fn has_cpu_flag(cpuinfo: &str, name: &str) -> bool {
+    cpuinfo
+        .lines()
+        .find(|line| line.starts_with("flags"))
+        .and_then(|line| line.split_once(':'))
+        .is_some_and(|(_, flags)| flags.split_whitespace().any(|it| it == name))
+}
+
+#[cfg(target_arch = "x86_64")]
+#[allow(unused_unsafe)]
+fn cpuid_invariant_tsc() -> Option<bool> {
+    use core::arch::x86_64::__cpuid;
+
+    let max_extended = unsafe { __cpuid(0x8000_0000) }.eax;
+    if max_extended < 0x8000_0007 {
+        return None;
+    }
+
+    Some(unsafe { __cpuid(0x8000_0007) }.edx & (1 << 8) != 0)
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn cpuid_invariant_tsc() -> Option<bool> {
+    None
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimerQuality {
+    pub uses_os_clock: bool,
+    // smallest non-zero step between two reads, in clocks
+    pub resolution: u64,
+    // clocks per read, averaged over back to back reads
+    pub read_overhead: f64,
+}
+
+impl TimerQuality {
+    const READS: u64 = 10_000;
+
+    pub fn measure(read: impl Fn() -> u64) -> (u64, f64) {
+        let mut resolution = u64::MAX;
+        let start = read();
+        let mut prev = start;
+        for _ in 0..TimerQuality::READS {
+            let now = read();
+            let step = now.wrapping_sub(prev);
+            if step != 0 {
+                resolution = resolution.min(step);
+            }
+            prev = now;
+        }
+
+        let overhead = prev.wrapping_sub(start) as f64 / TimerQuality::READS as f64;
+        (resolution, overhead)
+    }
+}
+
+impl TimeMeasurer {
+    pub fn quality(&self) -> TimerQuality {
+        let (resolution, read_overhead) = TimerQuality::measure(|| self.clocks_now());
+
+        TimerQuality {
+            uses_os_clock: self.uses_os_clock(),
+            resolution,
+            read_overhead,
+        }
+    }
+}
+
+#[test]
+fn tsc_problems() {
+    let reliable = TscInfo {
+        invariant_tsc: Some(true),
+        constant_tsc: Some(true),
+        nonstop_tsc: Some(true),
+        tsc_known_freq: Some(true),
+        clocksource: Some("tsc".to_string()),
+    };
+    assert!(reliable.problems().is_empty());
+    assert!(reliable.warnings().is_empty());
+
+    let calibrated = TscInfo {
+        tsc_known_freq: Some(false),
+        ..reliable.clone()
+    };
+    assert!(calibrated.problems().is_empty());
+    assert_eq!(
+        calibrated.warnings(),
+        ["/proc/cpuinfo has no tsc_known_freq flag, the TSC frequency may be spin calibrated"]
+    );
+
+    let unknown = TscInfo {
+        invariant_tsc: None,
+        constant_tsc: None,
+        nonstop_tsc: None,
+        tsc_known_freq: None,
+        clocksource: None,
+    };
+    assert!(unknown.problems().is_empty());
+    assert!(unknown.warnings().is_empty());
+
+    // a VM guest with invariant TSC keeps RDTSC
+    let guest = TscInfo {
+        clocksource: Some("kvm-clock".to_string()),
+        ..reliable.clone()
+    };
+    assert!(guest.problems().is_empty());
+    assert_eq!(
+        guest.warnings(),
+        ["kernel clocksource is 'kvm-clock', not 'tsc'"]
+    );
+
+    let unstable = TscInfo {
+        invariant_tsc: Some(false),
+        clocksource: Some("hpet".to_string()),
+        ..reliable
+    };
+    assert_eq!(unstable.problems(), ["CPUID reports no invariant TSC"]);
+}
+
+#[test]
+fn reads_cpu_flags() {
+    let cpuinfo = "processor\t: 0\nflags\t\t: fpu tsc constant_tsc nonstop_tsc_s\n";
+
+    assert!(has_cpu_flag(cpuinfo, "constant_tsc"));
+    assert!(has_cpu_flag(cpuinfo, "tsc"));
+    assert!(!has_cpu_flag(cpuinfo, "nonstop_tsc"));
+}
+
+#[test]
+fn measures_resolution_and_overhead() {
+    use std::cell::Cell;
+
+    // a counter which ticks by 4 on every read, and only every other read
+    let reads = Cell::new(0u64);
+    let (resolution, overhead) = TimerQuality::measure(|| {
+        reads.set(reads.get() + 1);
+        reads.get() / 2 * 4
+    });
+
+    assert_eq!(resolution, 4);
+    assert!((overhead - 2.0).abs() < 0.01);
+}
@@ -0,0 +1,282 @@
+use std::{cell::Cell, env, time::Duration};
+
+use super::{
+    TimeMeasurer,
+    frequency::{ClockFrequency, FrequencyMethod, counter_frequency},
+    spin_frequency,
+};
+use crate::perf_event::CycleCounter;
+
+// a source of monotonic ticks, picked at runtime so backends can be compared in one binary
+pub trait Timer {
+    fn kind(&self) -> TimerKind;
+    fn now(&self) -> u64;
+
+    // serialized reads around short blocks, so the block can't leak out of the window
+    fn start(&self) -> u64 {
+        self.now()
+    }
+    fn end(&self) -> u64 {
+        self.now()
+    }
+
+    // ticks per second, spin calibrated on every call unless the timer knows better
+    fn frequency(&self, max_estimation_time: Duration) -> ClockFrequency {
+        ClockFrequency {
+            hz: spin_frequency(|| self.now(), max_estimation_time),
+            method: FrequencyMethod::Calibrated,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum TimerKind {
+    // `TimeMeasurer`, whatever the features and the TSC checks picked
+    #[default]
+    Default,
+    Rdtsc,
+    // waits for all earlier instructions, the lfence keeps later ones from starting early
+    RdtscpLfence,
+    Monotonic,
+    // not slewed by NTP
+    MonotonicRaw,
+    // user space cycles of the calling thread, stops while it's descheduled
+    PerfCycles,
+}
+
+pub const TIMER_ENV: &str = "TIME_TIMER";
+
+impl TimerKind {
+    pub const ALL: [TimerKind; 6] = [
+        TimerKind::Default,
+        TimerKind::Rdtsc,
+        TimerKind::RdtscpLfence,
+        TimerKind::Monotonic,
+        TimerKind::MonotonicRaw,
+        TimerKind::PerfCycles,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TimerKind::Default => "default",
+            TimerKind::Rdtsc => "rdtsc",
+            TimerKind::RdtscpLfence => "rdtscp",
+            TimerKind::Monotonic => "monotonic",
+            TimerKind::MonotonicRaw => "monotonic_raw",
+            TimerKind::PerfCycles => "perf_cycles",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<TimerKind> {
+        TimerKind::ALL.into_iter().find(|it| it.name() == name)
+    }
+
+    // `TIME_TIMER=rdtscp`
+    pub fn from_env() -> TimerKind {
+        match env::var(TIMER_ENV) {
+            Err(_) => TimerKind::Default,
+            Ok(name) => TimerKind::from_name(&name).unwrap_or_else(|| {
+                let names: Vec<&str> = TimerKind::ALL.iter().map(|it| it.name()).collect();
+                panic!(
+                    "{} must be one of {}, got '{}'",
+                    TIMER_ENV,
+                    names.join(", "),
+                    name
+                )
+            }),
+        }
+    }
+
+    // None when the platform or the permissions don't have it
+    pub fn create(self) -> Option<Box<dyn Timer>> {
+        match self {
+            TimerKind::Default => TimeMeasurer::init().map(|it| Box::new(it) as Box<dyn Timer>),
+            #[cfg(target_arch = "x86_64")]
+            TimerKind::Rdtsc => Some(Box::new(Rdtsc)),
+            #[cfg(target_arch = "x86_64")]
+            TimerKind::RdtscpLfence => Some(Box::new(RdtscpLfence)),
+            #[cfg(not(target_arch = "x86_64"))]
+            TimerKind::Rdtsc | TimerKind::RdtscpLfence => None,
+            #[cfg(unix)]
+            TimerKind::Monotonic => Some(Box::new(ClockGettime {
+                kind: self,
+                clock: libc::CLOCK_MONOTONIC,
+            })),
+            #[cfg(any(target_os = "linux", target_vendor = "apple"))]
+            TimerKind::MonotonicRaw => Some(Box::new(ClockGettime {
+                kind: self,
+                clock: libc::CLOCK_MONOTONIC_RAW,
+            })),
+            #[cfg(not(unix))]
+            TimerKind::Monotonic => None,
+            #[cfg(not(any(target_os = "linux", target_vendor = "apple")))]
+            TimerKind::MonotonicRaw => None,
+            TimerKind::PerfCycles => {
+                let counter = CycleCounter::open()?;
+                let last = Cell::new(counter.read()?);
+                Some(Box::new(PerfCycles { counter, last }))
+            }
+        }
+    }
+}
+
+impl Timer for TimeMeasurer {
+    fn kind(&self) -> TimerKind {
+        TimerKind::Default
+    }
+
+    #[inline(always)]
+    fn now(&self) -> u64 {
+        self.clocks_now()
+    }
+
+    #[inline(always)]
+    fn start(&self) -> u64 {
+        self.clocks_start()
+    }
+
+    #[inline(always)]
+    fn end(&self) -> u64 {
+        self.clocks_end()
+    }
+
+    fn frequency(&self, max_estimation_time: Duration) -> ClockFrequency {
+        self.clock_frequency(max_estimation_time)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+struct Rdtsc;
+
+#[cfg(target_arch = "x86_64")]
+impl Timer for Rdtsc {
+    fn kind(&self) -> TimerKind {
+        TimerKind::Rdtsc
+    }
+
+    #[inline(always)]
+    fn now(&self) -> u64 {
+        unsafe { core::arch::x86_64::_rdtsc() }
+    }
+
+    #[inline(always)]
+    fn start(&self) -> u64 {
+        use core::arch::x86_64::{_mm_lfence, _rdtsc};
+
+        unsafe {
+            _mm_lfence();
+            _rdtsc()
+        }
+    }
+
+    #[inline(always)]
+    fn end(&self) -> u64 {
+        RdtscpLfence.now()
+    }
+
+    fn frequency(&self, max_estimation_time: Duration) -> ClockFrequency {
+        counter_frequency(|| self.now(), max_estimation_time)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+struct RdtscpLfence;
+
+#[cfg(target_arch = "x86_64")]
+impl Timer for RdtscpLfence {
+    fn kind(&self) -> TimerKind {
+        TimerKind::RdtscpLfence
+    }
+
+    #[inline(always)]
+    fn now(&self) -> u64 {
+        use core::arch::x86_64::{__rdtscp, _mm_lfence};
+
+        let mut aux = 0;
+        unsafe {
+            let now = __rdtscp(&mut aux);
+            _mm_lfence();
+            now
+        }
+    }
+
+    fn frequency(&self, max_estimation_time: Duration) -> ClockFrequency {
+        counter_frequency(|| self.now(), max_estimation_time)
+    }
+}
+
+#[cfg(unix)]
+struct ClockGettime {
+    kind: TimerKind,
+    clock: libc::clockid_t,
+}
+
+#[cfg(unix)]
+impl Timer for ClockGettime {
+    fn kind(&self) -> TimerKind {
+        self.kind
+    }
+
+    // nanoseconds
+    fn now(&self) -> u64 {
+        let mut spec = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        let code = unsafe { libc::clock_gettime(self.clock, &mut spec) };
+        assert!(code == 0);
+
+        spec.tv_sec as u64 * 1_000_000_000 + spec.tv_nsec as u64
+    }
+
+    fn frequency(&self, _max_estimation_time: Duration) -> ClockFrequency {
+        ClockFrequency::OS_CLOCK
+    }
+}
+
+struct PerfCycles {
+    counter: CycleCounter,
+    last: Cell<u64>,
+}
+
+impl Timer for PerfCycles {
+    fn kind(&self) -> TimerKind {
+        TimerKind::PerfCycles
+    }
+
+    fn now(&self) -> u64 {
+        // a failed read repeats the last value, 0 would wrap the next difference to ~u64::MAX
+        match self.counter.read() {
+            Some(cycles) => {
+                self.last.set(cycles);
+                cycles
+            }
+            None => self.last.get(),
+        }
+    }
+}
+
+#[test]
+fn parses_timer_names() {
+    for kind in TimerKind::ALL {
+        assert_eq!(TimerKind::from_name(kind.name()), Some(kind));
+    }
+    assert_eq!(TimerKind::from_name("hpet"), None);
+}
+
+#[test]
+fn available_timers_are_monotonic() {
+    for kind in TimerKind::ALL {
+        let Some(timer) = kind.create() else {
+            continue;
+        };
+        assert_eq!(timer.kind(), kind);
+
+        let first = timer.now();
+        let mut spin = 0u64;
+        for it in 0..100_000 {
+            spin = std::hint::black_box(spin + it);
+        }
+        assert!(timer.now() > first, "{} did not advance", kind.name());
+    }
+}